@@ -1,3 +1,5 @@
+#![allow(clippy::clone_on_copy)]
+
 use scroll_derive::{Pread, Pwrite, SizeWith};
 
 /// An example of using a method as the value for a ctx in a derive.
@@ -25,7 +27,7 @@ impl<'a> TryFromCtx<'a, usize> for VariableLengthData {
         Ok((Self { buf }, *offset))
     }
 }
-impl<'a> TryIntoCtx<usize> for &'a VariableLengthData {
+impl TryIntoCtx<usize> for &VariableLengthData {
     type Error = scroll::Error;
     fn try_into_ctx(self, dst: &mut [u8], ctx: usize) -> Result<usize, Self::Error> {
         let offset = &mut 0;
@@ -51,6 +53,7 @@ struct Data {
     arr: [u16; 2],
     // You can use arbitrary expressions for the ctx.
     // You have access to the `ctx` parameter of the `{pread/gread}_with` inside the expression.
+    // NB: `ctx` is a reference inside `SizeWith`, hence the `clone` to work in both places.
     #[scroll(ctx = EndianDependent(ctx.clone()).len())]
     custom_ctx: VariableLengthData,
 }
@@ -22,7 +22,7 @@ fn main() {
     }
     let services = services(&bytes);
     use Service::*;
-    for (s1, s2) in services.zip([Good, Ugly, Bad].into_iter()) {
+    for (s1, s2) in services.zip([Good, Ugly, Bad]) {
         println!("{s1:?},{s2:?}");
         assert_eq!(s1, s2);
     }
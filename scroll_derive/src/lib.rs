@@ -22,10 +22,7 @@ fn extract_idents_and_offset(
         })
         .collect();
     // iterate until we have no field that matches our offset
-    let offset = fresh_name(
-        &fields,
-        proc_macro2::Ident::new("offset", Span::call_site()),
-    );
+    let offset = fresh_name(fields, proc_macro2::Ident::new("offset", Span::call_site()));
 
     (idents, offset)
 }
@@ -34,11 +31,11 @@ fn extract_idents_and_offset(
 /// NB: there is probably a more efficient algorithm than this worst case O^2 runtime, but even for
 /// a struct with hundreds of fields all clashing with increasing _ prefixes, which is a highly
 /// degenerate example input, it is fine.
-fn fresh_name(
-    fields: &syn::punctuated::Punctuated<syn::Field, syn::token::Comma>,
+fn fresh_name<'a>(
+    fields: impl IntoIterator<Item = &'a syn::Field> + Clone,
     mut target: proc_macro2::Ident,
 ) -> Ident {
-    while fields.iter().any(|f| {
+    while fields.clone().into_iter().any(|f| {
         f.ident
             .as_ref()
             .map(|ident| ident == &target)
@@ -49,6 +46,63 @@ fn fresh_name(
     target
 }
 
+/// How an error raised while reading or writing `field` of `name` is reported. As tokens, it is the
+/// closure adding the field to the breadcrumbs of the error, with `offset` still at the start of
+/// the field; with a `#[scroll(error = Type)]` there are no breadcrumbs, and it converts the error
+/// to that type instead.
+struct FieldContext<'a> {
+    name: String,
+    field: String,
+    offset: &'a Ident,
+    error: Option<&'a syn::Type>,
+}
+
+fn field_context<'a>(
+    name: &str,
+    field: &str,
+    offset: &'a Ident,
+    container: &'a ContainerAttrs,
+) -> FieldContext<'a> {
+    FieldContext {
+        name: name.to_owned(),
+        field: field.to_owned(),
+        offset,
+        error: container.error.as_ref(),
+    }
+}
+
+impl FieldContext<'_> {
+    /// The closure adding the element at `index` of the field to the breadcrumbs of an error
+    fn at_index(&self, index: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let offset = self.offset;
+        match self.error {
+            Some(_) => quote! { |e| e },
+            None => quote! {
+                |e| ::scroll::Error::at_index(::core::convert::Into::into(e), #index, *#offset)
+            },
+        }
+    }
+}
+
+impl ToTokens for FieldContext<'_> {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let FieldContext {
+            name,
+            field,
+            offset,
+            error,
+        } = self;
+        tokens.extend(match error {
+            Some(error) => quote! {
+                |e| -> #error { ::core::convert::Into::into(e) }
+            },
+            None => quote! {
+                |e| ::scroll::Error::in_field(::core::convert::Into::into(e), #name, #field, *#offset)
+            },
+        });
+    }
+}
+
 fn extract_lifetime(
     gp: &syn::punctuated::Punctuated<syn::GenericParam, syn::token::Comma>,
 ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
@@ -66,7 +120,7 @@ fn extract_lifetime(
         .pop()
         .unwrap_or(syn::Lifetime::new("'a", Span::call_site()));
     // alpha rename/make the thing fresh
-    let alpha = format!("'{}_fresh", lifetime.ident.to_string());
+    let alpha = format!("'{}_fresh", lifetime.ident);
     (
         lifetime.to_token_stream(),
         syn::Lifetime::new(&alpha.to_string(), lifetime.span()).to_token_stream(),
@@ -78,80 +132,1217 @@ fn impl_field(
     ty: &syn::Type,
     src: &proc_macro2::Ident,
     default_ctx: &proc_macro2::TokenStream,
-    custom_ctx: Option<&proc_macro2::TokenStream>,
+    attrs: &FieldAttrs,
     offset: &Ident,
-    noctx: bool,
+    context: &FieldContext,
 ) -> proc_macro2::TokenStream {
-    let ctx = custom_ctx.unwrap_or(default_ctx);
-    match ty {
-        syn::Type::Group(group) => impl_field(
-            ident,
-            &group.elem,
+    if let Some(assert) = &attrs.assert {
+        let attrs = FieldAttrs {
+            assert: None,
+            ..attrs.clone()
+        };
+        let read = impl_field(ident, ty, src, default_ctx, &attrs, offset, context);
+        let msg = format!("assertion failed: {}", assert.to_token_stream());
+        let assert = strip_self(assert.to_token_stream());
+        return quote! {
+            let __start = *#offset;
+            #read
+            if !(#assert) {
+                // report the failure at the start of the field
+                let __size = *#offset - __start;
+                *#offset = __start;
+                return Err(::scroll::Error::BadInput { size: __size, msg: #msg }).map_err(#context);
+            }
+        };
+    }
+    if attrs.has_prelude() {
+        let prelude = impl_prelude(attrs, src, offset, context);
+        let attrs = attrs.without_prelude();
+        let read = impl_field(ident, ty, src, default_ctx, &attrs, offset, context);
+        return quote! { #prelude #read };
+    }
+    if let Some(cond) = &attrs.cond {
+        let cond = strip_self(cond.to_token_stream());
+        let (ty, attrs) = attrs.present(ty);
+        let read = impl_field(
+            &quote! { __value },
+            &ty,
             src,
             default_ctx,
-            custom_ctx,
+            &attrs,
             offset,
-            noctx,
-        ),
+            context,
+        );
+        return quote! {
+            let #ident = if #cond {
+                #read
+                ::core::option::Option::Some(__value)
+            } else {
+                ::core::option::Option::None
+            };
+        };
+    }
+    let ctx = attrs.ctx.as_ref().unwrap_or(default_ctx);
+    if let Some(read_with) = &attrs.read_with {
+        return quote! {
+            let #ident = #read_with(#src, #offset, #ctx).map_err(#context)?;
+        };
+    }
+    if let Some(len) = &attrs.len {
+        return impl_len_field(ident, ty, src, ctx, len, offset, context);
+    }
+    match ty {
+        syn::Type::Group(group) => {
+            impl_field(ident, &group.elem, src, default_ctx, attrs, offset, context)
+        }
         _ => {
-            if noctx {
+            if attrs.noctx {
                 quote! {
-                    let #ident = #src.gread::<#ty>(#offset)?;
+                    let #ident = #src.gread::<#ty>(#offset).map_err(#context)?;
                 }
             } else {
                 quote! {
-                    let #ident = #src.gread_with::<#ty>(#offset, #ctx)?;
+                    let #ident = #src.gread_with::<#ty>(#offset, #ctx).map_err(#context)?;
                 }
             }
         }
     }
 }
 
-/// Retrieve the field attribute with given ident e.g:
-/// ```ignore
-/// #[attr_ident(..)]
-/// field: T,
-/// ```
-fn get_attr<'a>(attr_ident: &str, field: &'a syn::Field) -> Option<&'a syn::Attribute> {
-    field
-        .attrs
-        .iter()
-        .find(|attr| attr.path().is_ident(attr_ident))
+/// Skips the padding and alignment preceding a field, and checks its magic
+fn impl_prelude(
+    attrs: &FieldAttrs,
+    src: &proc_macro2::Ident,
+    offset: &Ident,
+    context: &FieldContext,
+) -> proc_macro2::TokenStream {
+    let pad = attrs.pad.as_ref().map(|pad| {
+        quote! {
+            #src.gread_with::<&[u8]>(#offset, #pad).map_err(#context)?;
+        }
+    });
+    let align = attrs.align.as_ref().map(|align| {
+        quote! {
+            #src.gread_with::<&[u8]>(#offset, (#align - *#offset % #align) % #align)
+                .map_err(#context)?;
+        }
+    });
+    let magic = attrs
+        .magic
+        .as_ref()
+        .map(|magic| impl_magic(magic, src, offset, context));
+    quote! { #pad #align #magic }
 }
 
-/// Gets the `TokenStream` for the custom ctx set in the `ctx` attribute. e.g. `expr` in the following
-/// ```ignore
-/// #[scroll(ctx = expr)]
-/// field: T,
-/// ```
-fn custom_ctx(field: &syn::Field, noctx: &mut bool) -> Option<proc_macro2::TokenStream> {
-    get_attr("scroll", field).and_then(|x| {
-        // parsed #[scroll..]
-        // `expr` is `None` if the `ctx` key is not used.
-        let mut attrib = None;
-        let res = x.parse_nested_meta(|meta| {
-            // parsed #[scroll(..)]
-            if meta.path.is_ident("ctx") {
-                // parsed #[scroll(ctx..)]
-                let value = meta.value()?; // parsed #[scroll(ctx = ..)]
-                attrib = Some(value.parse::<syn::Expr>()?.into_token_stream()); // parsed #[scroll(ctx = expr)]
-                return Ok(());
+/// Reads and checks a magic, reporting a mismatch as `BadInput` at the start of the magic
+fn impl_magic(
+    magic: &syn::Expr,
+    src: &proc_macro2::Ident,
+    offset: &Ident,
+    context: &FieldContext,
+) -> proc_macro2::TokenStream {
+    quote! {
+        {
+            let __magic: &[u8] = #magic;
+            let __found = #src.pread_with::<&[u8]>(*#offset, __magic.len()).map_err(#context)?;
+            if __found != __magic {
+                return Err(::scroll::Error::BadInput {
+                    size: __magic.len(),
+                    msg: "bad magic",
+                })
+                .map_err(#context);
+            }
+            *#offset += __magic.len();
+        }
+    }
+}
+
+/// Writes zeros for the padding and alignment preceding a field, and its magic
+fn impl_pwrite_prelude(
+    attrs: &FieldAttrs,
+    dst: &Ident,
+    offset: &Ident,
+    context: &FieldContext,
+) -> proc_macro2::TokenStream {
+    let pad = attrs.pad.as_ref().map(|pad| {
+        quote! {
+            #dst.gwrite_with::<&[u8]>(&[0u8; #pad], #offset, ()).map_err(#context)?;
+        }
+    });
+    let align = attrs.align.as_ref().map(|align| {
+        quote! {
+            #dst.gwrite_with::<&[u8]>(
+                &[0u8; #align][..(#align - *#offset % #align) % #align],
+                #offset,
+                (),
+            )
+            .map_err(#context)?;
+        }
+    });
+    let magic = attrs
+        .magic
+        .as_ref()
+        .map(|magic| impl_pwrite_magic(magic, dst, offset, context));
+    quote! { #pad #align #magic }
+}
+
+fn impl_pwrite_magic(
+    magic: &syn::Expr,
+    dst: &Ident,
+    offset: &Ident,
+    context: &FieldContext,
+) -> proc_macro2::TokenStream {
+    quote! {
+        {
+            let __magic: &[u8] = #magic;
+            #dst.gwrite_with(__magic, #offset, ()).map_err(#context)?;
+        }
+    }
+}
+
+/// Reads a `Vec<T>`, `&[u8]` or `&str` field whose length is given by `len`; `Vec` elements are
+/// read with `ctx`, and so is a length prefix
+fn impl_len_field(
+    ident: &proc_macro2::TokenStream,
+    ty: &syn::Type,
+    src: &proc_macro2::Ident,
+    ctx: &proc_macro2::TokenStream,
+    len: &FieldLen,
+    offset: &Ident,
+    context: &FieldContext,
+) -> proc_macro2::TokenStream {
+    let collection = Collection::of(ty).expect("checked while parsing the attributes");
+    let count = match len {
+        FieldLen::Count(expr) => {
+            let expr = strip_self(expr.to_token_stream());
+            quote! {
+                ::core::convert::TryFrom::try_from(#expr).map_err(|_| ::scroll::Error::BadInput {
+                    size: *#offset,
+                    msg: "count doesn't fit in a usize",
+                }).map_err(#context)?
+            }
+        }
+        FieldLen::Prefix(prefix) => quote! {
+            ::core::convert::TryFrom::try_from(
+                #src.gread_with::<#prefix>(#offset, #ctx).map_err(#context)?
+            ).map_err(|_| ::scroll::Error::BadInput {
+                size: *#offset,
+                msg: "length prefix doesn't fit in a usize",
+            }).map_err(#context)?
+        },
+        FieldLen::UntilEof => quote! { #src.len().saturating_sub(*#offset) },
+    };
+    match collection {
+        Collection::Bytes => quote! {
+            let #ident = {
+                let __count: usize = #count;
+                #src.gread_with::<#ty>(#offset, __count).map_err(#context)?
+            };
+        },
+        Collection::Str => quote! {
+            let #ident = {
+                let __count: usize = #count;
+                #src.gread_with::<#ty>(#offset, ::scroll::ctx::StrCtx::Length(__count))
+                    .map_err(#context)?
+            };
+        },
+        // the offset is still at the start of the failed element, so that is where the index
+        // context puts the error
+        Collection::Vec(elem) => {
+            let index_context = context.at_index(quote! { __i });
+            let read = quote! {
+                #src.gread_with::<#elem>(#offset, #ctx)
+                    .map_err(#index_context)
+                    .map_err(#context)?
+            };
+            match len {
+                FieldLen::UntilEof => quote! {
+                    let #ident = {
+                        let mut __vec = ::scroll::export::vec::Vec::new();
+                        while *#offset < #src.len() {
+                            let (__i, __start) = (__vec.len(), *#offset);
+                            __vec.push(#read);
+                            if *#offset == __start {
+                                return Err(::scroll::Error::BadInput {
+                                    size: 0,
+                                    msg: "zero sized element while reading until the end",
+                                }).map_err(#context);
+                            }
+                        }
+                        __vec
+                    };
+                },
+                _ => quote! {
+                    let #ident = {
+                        let __count: usize = #count;
+                        // the count usually comes from the input, so don't trust it for the allocation
+                        let mut __vec = ::scroll::export::vec::Vec::with_capacity(
+                            ::core::cmp::min(__count, #src.len()),
+                        );
+                        for __i in 0..__count {
+                            __vec.push(#read);
+                        }
+                        __vec
+                    };
+                },
+            }
+        }
+    }
+}
+
+/// How the length of a variable length field is determined
+#[derive(Clone)]
+enum FieldLen {
+    /// `#[scroll(count = expr)]`: the number of elements (or bytes), usually from an earlier field
+    Count(syn::Expr),
+    /// `#[scroll(len_prefix = Type)]`: the number of elements is a `Type` preceding the field
+    Prefix(syn::Type),
+    /// `#[scroll(until_eof)]`: the field extends to the end of the input
+    UntilEof,
+}
+
+/// The fields a [FieldLen] can be given for
+enum Collection<'a> {
+    /// `Vec<T>`, with its element type
+    Vec(&'a syn::Type),
+    /// `&[u8]`
+    Bytes,
+    /// `&str`
+    Str,
+}
+
+impl<'a> Collection<'a> {
+    fn of(ty: &'a syn::Type) -> Option<Self> {
+        match ty {
+            syn::Type::Group(group) => Self::of(&group.elem),
+            syn::Type::Reference(reference) => match &*reference.elem {
+                syn::Type::Slice(slice) if is_u8(&slice.elem) => Some(Collection::Bytes),
+                syn::Type::Path(path) if path.path.is_ident("str") => Some(Collection::Str),
+                _ => None,
+            },
+            _ => type_argument(ty, "Vec").map(Collection::Vec),
+        }
+    }
+}
+
+fn is_u8(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Group(group) => is_u8(&group.elem),
+        syn::Type::Path(path) => path.path.is_ident("u8"),
+        _ => false,
+    }
+}
+
+/// The `T` of a `Wrapper<T>` type like `Vec<T>` or `Option<T>`
+fn type_argument<'a>(ty: &'a syn::Type, wrapper: &str) -> Option<&'a syn::Type> {
+    match ty {
+        syn::Type::Group(group) => type_argument(&group.elem, wrapper),
+        syn::Type::Path(path) => {
+            let segment = path.path.segments.last()?;
+            if segment.ident != wrapper {
+                return None;
+            }
+            match &segment.arguments {
+                syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+                    Some(syn::GenericArgument::Type(ty)) => Some(ty),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// `#[scroll(endian = "..")]`: a fixed byte order, instead of the one given by the context
+#[derive(Clone, Copy)]
+enum EndianAttr {
+    Big,
+    Little,
+    Native,
+    /// The byte order of the context after all, e.g. for a field in a big endian container
+    Ctx,
+}
+
+impl EndianAttr {
+    fn parse(meta: &syn::meta::ParseNestedMeta) -> syn::Result<Self> {
+        let endian: syn::LitStr = meta.value()?.parse()?;
+        match endian.value().as_str() {
+            "big" => Ok(EndianAttr::Big),
+            "little" => Ok(EndianAttr::Little),
+            "native" => Ok(EndianAttr::Native),
+            "ctx" => Ok(EndianAttr::Ctx),
+            _ => Err(syn::Error::new_spanned(
+                endian,
+                r#"endian must be one of "big", "little", "native" or "ctx""#,
+            )),
+        }
+    }
+
+    /// The context a field is read and written with, given the context of the impl
+    fn ctx(self, ctx: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self {
+            EndianAttr::Big => quote! { ::scroll::BE },
+            EndianAttr::Little => quote! { ::scroll::LE },
+            EndianAttr::Native => quote! { ::scroll::NATIVE },
+            EndianAttr::Ctx => ctx.clone(),
+        }
+    }
+}
+
+/// The `#[scroll(..)]` attributes of a field
+#[derive(Default, Clone)]
+struct FieldAttrs {
+    /// `#[scroll(ctx = expr)]`: the context the field is read and written with
+    ctx: Option<proc_macro2::TokenStream>,
+    /// `#[scroll(noctx)]`: the field is read and written with its default context
+    noctx: bool,
+    /// The length of a `Vec<T>`, `&[u8]` or `&str` field
+    len: Option<FieldLen>,
+    /// `#[scroll(if = expr)]`: an `Option<T>` field is only present when `expr` holds
+    cond: Option<syn::Expr>,
+    /// `#[scroll(pad = N)]`: `N` reserved bytes precede the field
+    pad: Option<syn::Expr>,
+    /// `#[scroll(align = N)]`: the field starts at a multiple of `N` from the start of the struct
+    align: Option<syn::Expr>,
+    /// `#[scroll(magic = b"..")]`: a constant signature precedes the field
+    magic: Option<syn::Expr>,
+    /// `#[scroll(endian = "..")]`: the byte order of the field
+    endian: Option<EndianAttr>,
+    /// `#[scroll(assert = expr)]`: a condition the field must satisfy once read
+    assert: Option<syn::Expr>,
+    /// `#[scroll(read_with = path::to_fn)]`: reads the field instead of `gread_with`
+    read_with: Option<syn::Path>,
+    /// `#[scroll(write_with = path::to_fn)]`: writes the field instead of `gwrite_with`
+    write_with: Option<syn::Path>,
+    /// `#[scroll(size_with = path::to_fn)]`: the size of the field instead of its `SizeWith`
+    size_with: Option<syn::Path>,
+}
+
+impl FieldAttrs {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut attrs = FieldAttrs::default();
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("scroll"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("ctx") {
+                    // parsed #[scroll(ctx = expr)]
+                    let value = meta.value()?;
+                    attrs.ctx = Some(value.parse::<syn::Expr>()?.into_token_stream());
+                    return Ok(());
+                }
+                if meta.path.is_ident("noctx") {
+                    // parsed #[scroll(noctx)]
+                    attrs.noctx = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("endian") {
+                    // parsed #[scroll(endian = "..")]
+                    attrs.endian = Some(EndianAttr::parse(&meta)?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("assert") {
+                    // parsed #[scroll(assert = expr)]
+                    attrs.assert = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("read_with") {
+                    // parsed #[scroll(read_with = path::to_fn)]
+                    attrs.read_with = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("write_with") {
+                    // parsed #[scroll(write_with = path::to_fn)]
+                    attrs.write_with = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("size_with") {
+                    // parsed #[scroll(size_with = path::to_fn)]
+                    attrs.size_with = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("pad") {
+                    // parsed #[scroll(pad = N)]
+                    attrs.pad = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("align") {
+                    // parsed #[scroll(align = N)]
+                    let align = meta.value()?.parse()?;
+                    if let syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Int(int),
+                        ..
+                    }) = &align
+                    {
+                        if int.base10_parse::<usize>()? == 0 {
+                            return Err(meta.error("align must not be 0"));
+                        }
+                    }
+                    attrs.align = Some(align);
+                    return Ok(());
+                }
+                if meta.path.is_ident("magic") {
+                    // parsed #[scroll(magic = b"..")]
+                    attrs.magic = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("if") {
+                    // parsed #[scroll(if = expr)]
+                    if type_argument(&field.ty, "Option").is_none() {
+                        return Err(meta.error("if needs an Option<T> field"));
+                    }
+                    attrs.cond = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("bits") {
+                    return Err(meta.error("bits needs a #[scroll(bitfield = Type)] struct"));
+                }
+                let len = if meta.path.is_ident("count") {
+                    // parsed #[scroll(count = expr)]
+                    FieldLen::Count(meta.value()?.parse()?)
+                } else if meta.path.is_ident("len_prefix") {
+                    // parsed #[scroll(len_prefix = Type)]
+                    FieldLen::Prefix(meta.value()?.parse()?)
+                } else if meta.path.is_ident("until_eof") {
+                    // parsed #[scroll(until_eof)]
+                    FieldLen::UntilEof
+                } else {
+                    return Err(meta.error(match meta.path.get_ident() {
+                        Some(ident) => format!("unrecognized attribute: {ident}"),
+                        None => "unrecognized and invalid attribute".to_owned(),
+                    }));
+                };
+                if attrs.len.is_some() {
+                    return Err(
+                        meta.error("only one of count, len_prefix and until_eof can be given")
+                    );
+                }
+                let ty = type_argument(&field.ty, "Option").unwrap_or(&field.ty);
+                if Collection::of(ty).is_none() {
+                    // only byte slices can be borrowed from the input as they are
+                    let msg = match ty {
+                        syn::Type::Reference(reference)
+                            if matches!(*reference.elem, syn::Type::Slice(_)) =>
+                        {
+                            "count, len_prefix and until_eof can't read a &[T] other than &[u8], use a Vec<T> instead"
+                        }
+                        _ => "count, len_prefix and until_eof need a Vec<T>, &[u8] or &str field",
+                    };
+                    return Err(meta.error(msg));
+                }
+                attrs.len = Some(len);
+                Ok(())
+            })?;
+        }
+        if attrs.ctx.is_some() && attrs.endian.is_some() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "only one of ctx and endian can be given",
+            ));
+        }
+        Ok(attrs)
+    }
+
+    /// The context of the field unless it has a `#[scroll(ctx = expr)]`, where `ctx` is the one of
+    /// the impl
+    fn default_ctx(
+        &self,
+        container: &ContainerAttrs,
+        ctx: &proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        match self.endian.or(container.endian) {
+            Some(endian) => endian.ctx(ctx),
+            None => ctx.clone(),
+        }
+    }
+
+    /// For an `Option<T>` field with `#[scroll(if = expr)]`, the `T` and the attributes it is read
+    /// and written with
+    fn present(&self, ty: &syn::Type) -> (syn::Type, Self) {
+        let ty = type_argument(ty, "Option").expect("checked while parsing the attributes");
+        let attrs = FieldAttrs {
+            cond: None,
+            ..self.without_prelude()
+        };
+        (ty.clone(), attrs)
+    }
+
+    /// Whether padding, alignment or a magic precede the field
+    fn has_prelude(&self) -> bool {
+        self.pad.is_some() || self.align.is_some() || self.magic.is_some()
+    }
+
+    /// The attributes of the field itself, after its prelude was handled
+    fn without_prelude(&self) -> Self {
+        FieldAttrs {
+            pad: None,
+            align: None,
+            magic: None,
+            ..self.clone()
+        }
+    }
+
+    /// Whether the field has an expression evaluated with the earlier fields and the ctx in scope
+    fn has_expr(&self) -> bool {
+        self.cond.is_some() || self.assert.is_some() || matches!(self.len, Some(FieldLen::Count(_)))
+    }
+
+    /// `IOread` and `IOwrite` go through `FromCtx` and `IntoCtx`, which can't fail, on a fixed
+    /// number of bytes, so they only support the attributes which can't fail or change the size
+    fn ensure_infallible(&self, field: &syn::Field, derive: &str) -> syn::Result<()> {
+        let unsupported = [
+            ("magic", self.magic.is_some()),
+            ("if", self.cond.is_some()),
+            ("count", matches!(self.len, Some(FieldLen::Count(_)))),
+            ("len_prefix", matches!(self.len, Some(FieldLen::Prefix(_)))),
+            ("until_eof", matches!(self.len, Some(FieldLen::UntilEof))),
+            ("assert", self.assert.is_some()),
+            ("read_with", self.read_with.is_some()),
+            ("write_with", self.write_with.is_some()),
+            ("size_with", self.size_with.is_some()),
+        ];
+        match unsupported.iter().find(|(_, set)| *set) {
+            Some((attr, _)) => Err(syn::Error::new_spanned(
+                field,
+                format!(
+                    "{derive} doesn't support `#[scroll({attr})]`, derive Pread and Pwrite instead"
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Parses the attributes of all of `fields`, rejecting field names which would clash with the
+    /// locals of the generated code
+    fn parse_all<'a>(
+        fields: impl IntoIterator<Item = &'a syn::Field> + Clone,
+    ) -> syn::Result<Vec<Self>> {
+        let attrs = fields
+            .clone()
+            .into_iter()
+            .map(Self::parse)
+            .collect::<syn::Result<Vec<_>>>()?;
+        let has_expr = attrs.iter().any(FieldAttrs::has_expr);
+        for ident in fields.into_iter().filter_map(|field| field.ident.as_ref()) {
+            if RESERVED_LOCALS.iter().any(|local| ident == local) {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    format!("`{ident}` is reserved for the generated code, rename the field"),
+                ));
+            }
+            // the ctx parameter would be renamed, so the expressions would see the field instead
+            if has_expr && ident == "ctx" {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "a field named `ctx` hides the ctx from the if, assert and count expressions, rename the field",
+                ));
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+/// The locals of the generated code which field attribute expressions could otherwise see in place
+/// of a field of the same name
+const RESERVED_LOCALS: &[&str] = &[
+    "__bits",
+    "__count",
+    "__element",
+    "__found",
+    "__i",
+    "__len",
+    "__magic",
+    "__size",
+    "__start",
+    "__tmp",
+    "__value",
+    "__vec",
+];
+
+/// Rewrites `self.field` to `field` in a field attribute expression, since while reading, the
+/// earlier fields are locals
+fn strip_self(tokens: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    use proc_macro2::TokenTree;
+    let mut out = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident == "self" => match tokens.peek() {
+                Some(TokenTree::Punct(punct)) if punct.as_char() == '.' => {
+                    tokens.next();
+                }
+                _ => out.push(TokenTree::Ident(ident)),
+            },
+            TokenTree::Group(group) => {
+                let mut stripped =
+                    proc_macro2::Group::new(group.delimiter(), strip_self(group.stream()));
+                stripped.set_span(group.span());
+                out.push(TokenTree::Group(stripped));
+            }
+            token => out.push(token),
+        }
+    }
+    out.into_iter().collect()
+}
+
+/// The `#[scroll(..)]` attributes of the type being derived
+#[derive(Default)]
+struct ContainerAttrs {
+    /// `#[scroll(ctx = Type)]`: the context the impls are generated for, instead of `Endian`
+    ctx: Option<syn::Type>,
+    /// `#[scroll(tag = Type)]`: the type of the tag preceding the fields of an enum's variants
+    tag: Option<syn::Type>,
+    /// `#[scroll(magic = b"..")]`: a constant signature preceding the fields (or the tag)
+    magic: Option<syn::Expr>,
+    /// `#[scroll(endian = "..")]`: the byte order of all fields without an `endian` of their own
+    endian: Option<EndianAttr>,
+    /// `#[scroll(validate = path::to_fn)]`: checks the invariants of a value once it is read
+    validate: Option<syn::Path>,
+    /// `#[scroll(error = Type)]`: the error of the `Pread` and `Pwrite` impls, instead of
+    /// `scroll::Error`
+    error: Option<syn::Type>,
+    /// `#[scroll(bitfield = Type)]`: the integer the fields are packed into, bit by bit
+    bitfield: Option<syn::Type>,
+    /// `#[scroll(bit_order = "..")]`: where the first field of a bitfield goes
+    bit_order: Option<BitOrder>,
+}
+
+impl ContainerAttrs {
+    /// Like [`FieldAttrs::ensure_infallible`], for the container attributes `IOread` and `IOwrite`
+    /// can't support, and the attributes of `fields`
+    fn ensure_infallible<'a>(
+        &self,
+        fields: impl IntoIterator<Item = &'a syn::Field>,
+        field_attrs: &[FieldAttrs],
+        derive: &str,
+    ) -> syn::Result<()> {
+        if let Some(magic) = &self.magic {
+            return Err(syn::Error::new_spanned(
+                magic,
+                format!(
+                    "{derive} doesn't support `#[scroll(magic)]`, derive Pread and Pwrite instead"
+                ),
+            ));
+        }
+        if let Some(validate) = &self.validate {
+            return Err(syn::Error::new_spanned(
+                validate,
+                format!(
+                    "{derive} doesn't support `#[scroll(validate)]`, derive Pread and Pwrite instead"
+                ),
+            ));
+        }
+        fields
+            .into_iter()
+            .zip(field_attrs)
+            .try_for_each(|(field, attrs)| attrs.ensure_infallible(field, derive))
+    }
+
+    fn parse(ast: &syn::DeriveInput) -> syn::Result<Self> {
+        let mut attrs = ContainerAttrs::default();
+        for attr in ast
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("scroll"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("ctx") {
+                    // parsed #[scroll(ctx = Type)]
+                    attrs.ctx = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("tag") {
+                    // parsed #[scroll(tag = Type)]
+                    attrs.tag = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("magic") {
+                    // parsed #[scroll(magic = b"..")]
+                    attrs.magic = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("endian") {
+                    // parsed #[scroll(endian = "..")]
+                    attrs.endian = Some(EndianAttr::parse(&meta)?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("validate") {
+                    // parsed #[scroll(validate = path::to_fn)]
+                    attrs.validate = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("error") {
+                    // parsed #[scroll(error = Type)]
+                    attrs.error = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("bitfield") {
+                    // parsed #[scroll(bitfield = Type)]
+                    attrs.bitfield = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("bit_order") {
+                    // parsed #[scroll(bit_order = "..")]
+                    attrs.bit_order = Some(BitOrder::parse(&meta)?);
+                    return Ok(());
+                }
+                Err(meta.error(match meta.path.get_ident() {
+                    Some(ident) => format!("unrecognized attribute: {ident}"),
+                    None => "unrecognized and invalid attribute".to_owned(),
+                }))
+            })?;
+        }
+        if attrs.bit_order.is_some() && attrs.bitfield.is_none() {
+            return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "bit_order needs a #[scroll(bitfield = Type)] struct",
+            ));
+        }
+        Ok(attrs)
+    }
+
+    /// The context of the type's own tag or discriminant, where `ctx` is the one of the impl
+    fn default_ctx(&self, ctx: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self.endian {
+            Some(endian) => endian.ctx(ctx),
+            None => ctx.clone(),
+        }
+    }
+
+    /// The error type of the generated impls
+    fn error_ty(&self) -> proc_macro2::TokenStream {
+        match &self.error {
+            Some(ty) => ty.to_token_stream(),
+            None => quote! { ::scroll::Error },
+        }
+    }
+
+    /// The `offset_error` of the generated impls, shifting the offset of the breadcrumbs added to
+    /// errors; there are none with a custom error type
+    fn offset_error(&self) -> proc_macro2::TokenStream {
+        match &self.error {
+            Some(_) => quote! {},
+            None => quote! {
+                #[inline]
+                fn offset_error(error: Self::Error, offset: usize) -> Self::Error {
+                    error.offset_by(offset)
+                }
+            },
+        }
+    }
+
+    /// The context type of the generated impls
+    fn ctx_ty(&self) -> proc_macro2::TokenStream {
+        match &self.ctx {
+            Some(ty) => ty.to_token_stream(),
+            None => quote! { ::scroll::Endian },
+        }
+    }
+
+    /// Fieldless enums read their discriminant with an `Endian`
+    fn ensure_endian_ctx(&self, derive: &str) {
+        if self.ctx.is_some() {
+            panic!("{derive} cannot be derived for enums with a custom ctx");
+        }
+        if self.magic.is_some() {
+            panic!("{derive} cannot be derived for fieldless enums with a magic");
+        }
+        if self.validate.is_some() {
+            panic!("{derive} cannot be derived for fieldless enums with a validate function");
+        }
+    }
+
+    /// Calls the `validate` function of the type, if it has one, on `value`
+    fn impl_validate(&self, value: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        self.validate
+            .as_ref()
+            .map_or_else(|| quote! {}, |validate| quote! { #validate(&#value)?; })
+    }
+
+    /// Reads and checks the magic of the type, if it has one
+    fn impl_magic(
+        &self,
+        name: &str,
+        src: &proc_macro2::Ident,
+        offset: &Ident,
+    ) -> proc_macro2::TokenStream {
+        self.magic.as_ref().map_or_else(
+            || quote! {},
+            |magic| {
+                impl_magic(
+                    magic,
+                    src,
+                    offset,
+                    &field_context(name, "magic", offset, self),
+                )
+            },
+        )
+    }
+
+    /// Writes the magic of the type, if it has one
+    fn impl_pwrite_magic(
+        &self,
+        name: &str,
+        dst: &Ident,
+        offset: &Ident,
+    ) -> proc_macro2::TokenStream {
+        self.magic.as_ref().map_or_else(
+            || quote! {},
+            |magic| {
+                impl_pwrite_magic(
+                    magic,
+                    dst,
+                    offset,
+                    &field_context(name, "magic", offset, self),
+                )
+            },
+        )
+    }
+}
+
+/// `#[scroll(bit_order = "..")]`: whether the first field of a bitfield takes the least or the most
+/// significant bits of the backing integer
+#[derive(Clone, Copy, Default)]
+enum BitOrder {
+    #[default]
+    Lsb,
+    Msb,
+}
+
+impl BitOrder {
+    fn parse(meta: &syn::meta::ParseNestedMeta) -> syn::Result<Self> {
+        let order: syn::LitStr = meta.value()?.parse()?;
+        match order.value().as_str() {
+            "lsb" => Ok(BitOrder::Lsb),
+            "msb" => Ok(BitOrder::Msb),
+            _ => Err(syn::Error::new_spanned(
+                order,
+                r#"bit_order must be one of "lsb" or "msb""#,
+            )),
+        }
+    }
+}
+
+/// The width of `ty` if it is an unsigned integer a bitfield can be packed into
+fn uint_bits(ty: &syn::Type) -> Option<u32> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    match path.path.get_ident()?.to_string().as_str() {
+        "u8" => Some(8),
+        "u16" => Some(16),
+        "u32" => Some(32),
+        "u64" => Some(64),
+        _ => None,
+    }
+}
+
+/// A field of a bitfield struct, `bits` wide and `shift` bits up from the least significant bit
+struct BitfieldMember<'a> {
+    /// The field's name, or its index in a tuple struct
+    member: proc_macro2::TokenStream,
+    ty: &'a syn::Type,
+    is_bool: bool,
+    bits: u32,
+    shift: u32,
+}
+
+impl BitfieldMember<'_> {
+    /// The largest value the field can hold, as a `u64`
+    fn mask(&self) -> proc_macro2::Literal {
+        proc_macro2::Literal::u64_unsuffixed(u64::MAX >> (64 - self.bits))
+    }
+}
+
+/// The layout of a `#[scroll(bitfield = Type)]` struct, whose fields are packed into an integer
+struct Bitfield<'a> {
+    name: &'a syn::Ident,
+    backing: &'a syn::Type,
+    members: Vec<BitfieldMember<'a>>,
+}
+
+impl<'a> Bitfield<'a> {
+    fn parse(
+        name: &'a syn::Ident,
+        fields: &'a syn::Fields,
+        generics: &syn::Generics,
+        container: &'a ContainerAttrs,
+    ) -> syn::Result<Self> {
+        let backing = container.bitfield.as_ref().expect("not a bitfield");
+        let width = uint_bits(backing).ok_or_else(|| {
+            syn::Error::new_spanned(backing, "a bitfield must be a u8, u16, u32 or u64")
+        })?;
+        if !generics.params.is_empty() {
+            return Err(syn::Error::new_spanned(
+                generics,
+                "bitfield structs can't be generic",
+            ));
+        }
+        if container.ctx.is_some() || container.tag.is_some() || container.magic.is_some() {
+            return Err(syn::Error::new_spanned(
+                name,
+                "bitfield structs are read with an Endian, and don't support ctx, tag or magic",
+            ));
+        }
+        let order = container.bit_order.unwrap_or_default();
+        let mut used = 0;
+        let mut members = Vec::new();
+        for (i, field) in fields.iter().enumerate() {
+            let bits = Self::bits(field)?;
+            let is_bool = matches!(&field.ty, syn::Type::Path(path) if path.path.is_ident("bool"));
+            let max = if is_bool {
+                1
+            } else {
+                uint_bits(&field.ty).ok_or_else(|| {
+                    syn::Error::new_spanned(
+                        &field.ty,
+                        "bitfield fields must be unsigned integers or bool",
+                    )
+                })?
+            };
+            if bits == 0 || bits > max {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    format!("bits must be between 1 and {max} for a field of this type"),
+                ));
+            }
+            if used + bits > width {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    format!("the fields don't fit in the {width} bits of the bitfield"),
+                ));
+            }
+            let shift = match order {
+                BitOrder::Lsb => used,
+                BitOrder::Msb => width - used - bits,
+            };
+            used += bits;
+            let member = match &field.ident {
+                Some(ident) => quote! { #ident },
+                None => proc_macro2::Literal::usize_unsuffixed(i).to_token_stream(),
+            };
+            members.push(BitfieldMember {
+                member,
+                ty: &field.ty,
+                is_bool,
+                bits,
+                shift,
+            });
+        }
+        Ok(Bitfield {
+            name,
+            backing,
+            members,
+        })
+    }
+
+    /// Parses the `#[scroll(bits = N)]` of a field, the only attribute of bitfield fields
+    fn bits(field: &syn::Field) -> syn::Result<u32> {
+        let mut bits = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("scroll"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("bits") {
+                    // parsed #[scroll(bits = N)]
+                    let lit: syn::LitInt = meta.value()?.parse()?;
+                    bits = Some(lit.base10_parse()?);
+                    return Ok(());
+                }
+                Err(meta.error("bitfield fields only support bits"))
+            })?;
+        }
+        bits.ok_or_else(|| {
+            syn::Error::new_spanned(field, "bitfield fields need a #[scroll(bits = N)]")
+        })
+    }
+
+    /// A value of the struct, unpacked from `bits`, the backing integer as a `u64`
+    fn unpack(&self, bits: &Ident) -> proc_macro2::TokenStream {
+        let members = self.members.iter().map(|m| {
+            let BitfieldMember {
+                member, ty, shift, ..
+            } = m;
+            let mask = m.mask();
+            if m.is_bool {
+                quote! { #member: ((#bits >> #shift) & #mask) != 0 }
+            } else {
+                quote! { #member: ((#bits >> #shift) & #mask) as #ty }
+            }
+        });
+        quote! { Self { #(#members,)* } }
+    }
+
+    /// The fields of `self` packed into the backing integer, where `overflow` handles a field
+    /// which doesn't fit in its bits; without any tokens from it, the field is truncated to them
+    fn pack(
+        &self,
+        overflow: impl Fn(&BitfieldMember) -> proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let backing = self.backing;
+        let members = self.members.iter().map(|m| {
+            let BitfieldMember { member, shift, .. } = m;
+            let mask = m.mask();
+            let overflow = if m.is_bool {
+                quote! {}
+            } else {
+                overflow(m)
+            };
+            let check = if overflow.is_empty() {
+                quote! {}
+            } else {
+                quote! {
+                    if __value > #mask {
+                        #overflow
+                    }
+                }
+            };
+            quote! {
+                let __value = self.#member as u64;
+                #check
+                __bits |= (__value & #mask) << #shift;
+            }
+        });
+        quote! {{
+            let mut __bits: u64 = 0;
+            #(#members)*
+            __bits as #backing
+        }}
+    }
+}
+
+fn impl_try_from_ctx_bitfield(
+    bitfield: &Bitfield,
+    container: &ContainerAttrs,
+) -> proc_macro2::TokenStream {
+    let Bitfield { name, backing, .. } = bitfield;
+    let error_ty = container.error_ty();
+    let ctx = container.default_ctx(&quote! { ctx });
+    let validate = container.impl_validate(&quote! { val });
+    let unpack = bitfield.unpack(&Ident::new("__bits", proc_macro2::Span::call_site()));
+    quote! {
+        impl<'a> ::scroll::ctx::TryFromCtx<'a, ::scroll::Endian> for #name {
+            type Error = #error_ty;
+            #[inline]
+            fn try_from_ctx(src: &'a [u8], ctx: ::scroll::Endian) -> ::scroll::export::result::Result<(Self, usize), Self::Error> {
+                use ::scroll::Pread;
+                let offset = &mut 0;
+                let __bits = src.gread_with::<#backing>(offset, #ctx)? as u64;
+                let val = #unpack;
+                #validate
+                Ok((val, *offset))
+            }
+        }
+    }
+}
+
+fn impl_try_into_ctx_bitfield(
+    bitfield: &Bitfield,
+    container: &ContainerAttrs,
+) -> proc_macro2::TokenStream {
+    let name = bitfield.name;
+    let error_ty = container.error_ty();
+    let offset_error = container.offset_error();
+    let ctx = container.default_ctx(&quote! { ctx });
+    let offset = Ident::new("offset", proc_macro2::Span::call_site());
+    let pack = bitfield.pack(|m| {
+        let context = field_context(
+            &name.to_string(),
+            &m.member.to_string(),
+            &offset,
+            container,
+        );
+        let size = m.bits as usize;
+        quote! {
+            return Err(::scroll::Error::BadInput { size: #size, msg: "value doesn't fit in its bits" }).map_err(#context);
+        }
+    });
+    quote! {
+        impl<'a> ::scroll::ctx::TryIntoCtx<::scroll::Endian> for &'a #name {
+            type Error = #error_ty;
+            #offset_error
+            #[inline]
+            fn try_into_ctx(self, dst: &mut [u8], ctx: ::scroll::Endian) -> ::scroll::export::result::Result<usize, Self::Error> {
+                use ::scroll::Pwrite;
+                let #offset = &mut 0;
+                let __bits = #pack;
+                dst.gwrite_with(__bits, #offset, #ctx)?;
+                Ok(*#offset)
+            }
+        }
+
+        impl ::scroll::ctx::TryIntoCtx<::scroll::Endian> for #name {
+            type Error = #error_ty;
+            #offset_error
+            #[inline]
+            fn try_into_ctx(self, dst: &mut [u8], ctx: ::scroll::Endian) -> ::scroll::export::result::Result<usize, Self::Error> {
+                (&self).try_into_ctx(dst, ctx)
+            }
+        }
+    }
+}
+
+fn impl_size_with_bitfield(bitfield: &Bitfield) -> proc_macro2::TokenStream {
+    let Bitfield { name, backing, .. } = bitfield;
+    quote! {
+        impl ::scroll::ctx::SizeWith<::scroll::Endian> for #name {
+            #[inline]
+            fn size_with(_ctx: &::scroll::Endian) -> usize {
+                ::scroll::export::mem::size_of::<#backing>()
+            }
+        }
+    }
+}
+
+fn impl_from_ctx_bitfield(
+    bitfield: &Bitfield,
+    container: &ContainerAttrs,
+) -> proc_macro2::TokenStream {
+    let Bitfield { name, backing, .. } = bitfield;
+    let ctx = container.default_ctx(&quote! { ctx });
+    let unpack = bitfield.unpack(&Ident::new("__bits", proc_macro2::Span::call_site()));
+    quote! {
+        impl ::scroll::ctx::FromCtx<::scroll::Endian> for #name {
+            #[inline]
+            fn from_ctx(src: &[u8], ctx: ::scroll::Endian) -> Self {
+                use ::scroll::Cread;
+                let __bits = src.cread_with::<#backing>(0, #ctx) as u64;
+                #unpack
+            }
+        }
+    }
+}
+
+fn impl_into_ctx_bitfield(
+    bitfield: &Bitfield,
+    container: &ContainerAttrs,
+) -> proc_macro2::TokenStream {
+    let name = bitfield.name;
+    let ctx = container.default_ctx(&quote! { ctx });
+    // `IntoCtx` can't fail, so rather than panic, a field which doesn't fit in its bits is truncated
+    let pack = bitfield.pack(|_| quote! {});
+    quote! {
+        impl<'a> ::scroll::ctx::IntoCtx<::scroll::Endian> for &'a #name {
+            #[inline]
+            fn into_ctx(self, dst: &mut [u8], ctx: ::scroll::Endian) {
+                use ::scroll::Cwrite;
+                let __bits = #pack;
+                dst.cwrite_with(__bits, 0, #ctx);
             }
-            if meta.path.is_ident("noctx") {
-                // parsed #[scroll(noctx)]
-                *noctx = true;
-                return Ok(());
+        }
+
+        impl ::scroll::ctx::IntoCtx<::scroll::Endian> for #name {
+            #[inline]
+            fn into_ctx(self, dst: &mut [u8], ctx: ::scroll::Endian) {
+                (&self).into_ctx(dst, ctx)
             }
-            Err(meta.error(match meta.path.get_ident() {
-                Some(ident) => format!("unrecognized attribute: {ident}"),
-                None => "unrecognized and invalid attribute".to_owned(),
-            }))
-        });
-        match res {
-            Ok(()) => attrib,
-            Err(e) => Some(e.into_compile_error()),
         }
-    })
+    }
 }
 
 fn impl_struct(
@@ -159,7 +1350,9 @@ fn impl_struct(
     fields: &syn::punctuated::Punctuated<syn::Field, syn::Token![,]>,
     generics: &syn::Generics,
     unnamed: bool,
+    container: &ContainerAttrs,
 ) -> proc_macro2::TokenStream {
+    let ctx_ty = &container.ctx_ty();
     let offset = fresh_name(
         fields,
         syn::Ident::new("offset", proc_macro2::Span::call_site()),
@@ -173,10 +1366,17 @@ fn impl_struct(
         syn::Ident::new("ctx", proc_macro2::Span::call_site()),
     )
     .to_token_stream();
+    let magic = container.impl_magic(&name.to_string(), &src, &offset);
+    let validate = container.impl_validate(&quote! { val });
+    let field_attrs = match FieldAttrs::parse_all(fields) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error(),
+    };
     let (items, item_assignments) = fields
         .iter()
+        .zip(&field_attrs)
         .enumerate()
-        .map(|(i, f)| {
+        .map(|(i, (f, attrs))| {
             let (ident, prefixed_ident) = &f
                 .ident
                 .as_ref()
@@ -192,18 +1392,16 @@ fn impl_struct(
                     (quote! {#t}, suf)
                 });
             let ty = &f.ty;
-            // parse the `expr` out of #[scroll(ctx = expr)]
-            let mut noctx = false;
-            let custom_ctx = custom_ctx(f, &mut noctx);
+            let context = field_context(&name.to_string(), &ident.to_string(), &offset, container);
             (
                 impl_field(
-                    &prefixed_ident,
+                    prefixed_ident,
                     ty,
                     &src,
-                    &ctx,
-                    custom_ctx.as_ref(),
+                    &attrs.default_ctx(container, &ctx),
+                    attrs,
                     &offset,
-                    noctx,
+                    &context,
                 ),
                 quote! { #ident: #prefixed_ident },
             )
@@ -237,14 +1435,21 @@ fn impl_struct(
         quote! {}
     };
 
+    let error_ty = container.error_ty();
+    let offset_error = container.offset_error();
     let gw = if !gp.is_empty() {
         let gi = gp.iter().filter_map(|param: &syn::GenericParam| match param {
             syn::GenericParam::Type(t) => Some({
                 let ident = &t.ident;
+                // a user error only needs to be convertible from the errors of the fields
+                let error = match container.error {
+                    Some(_) => quote! {},
+                    None => quote! { Error = ::scroll::Error },
+                };
                 quote! {
-                    #ident : ::scroll::ctx::TryFromCtx<#lifetime, ::scroll::Endian, Error = ::scroll::Error>,
-                    ::scroll::Error : ::std::convert::From<< #ident as ::scroll::ctx::TryFromCtx<#lifetime, ::scroll::Endian>>::Error>,
-                    < #ident as ::scroll::ctx::TryFromCtx<#lifetime, ::scroll::Endian>>::Error : ::std::convert::From<scroll::Error>
+                    #ident : ::scroll::ctx::TryFromCtx<#lifetime, #ctx_ty, #error>,
+                    #error_ty : ::std::convert::From<< #ident as ::scroll::ctx::TryFromCtx<#lifetime, #ctx_ty>>::Error>,
+                    < #ident as ::scroll::ctx::TryFromCtx<#lifetime, #ctx_ty>>::Error : ::std::convert::From<scroll::Error>
                 }
             }),
             syn::GenericParam::Lifetime(_) => None,
@@ -261,16 +1466,19 @@ fn impl_struct(
     };
 
     quote! {
-     impl<#lifetime, #lhs_gp > ::scroll::ctx::TryFromCtx<#lifetime, ::scroll::Endian> for #name #gn
+     impl<#lifetime, #lhs_gp > ::scroll::ctx::TryFromCtx<#lifetime, #ctx_ty> for #name #gn
          where #gw #name #gn : #lifetime {
-            // TODO: allow passing user error here
-            type Error = ::scroll::Error;
+            type Error = #error_ty;
+            #offset_error
             #[inline]
-            fn try_from_ctx(#src: &#lifetime [u8], #ctx: ::scroll::Endian) -> ::scroll::export::result::Result<(Self, usize), Self::Error> {
+            fn try_from_ctx(#src: &#lifetime [u8], #ctx: #ctx_ty) -> ::scroll::export::result::Result<(Self, usize), Self::Error> {
               use ::scroll::Pread;
               let #offset = &mut 0;
+              #magic
               #(#items)*
-              Ok((Self { #(#item_assignments,)* }, *#offset))
+              let val = Self { #(#item_assignments,)* };
+              #validate
+              Ok((val, *#offset))
             }
         }
     }
@@ -279,12 +1487,14 @@ fn impl_struct(
 fn ensure_fieldless(variants: &syn::punctuated::Punctuated<syn::Variant, syn::token::Comma>) {
     for variant in variants {
         if !variant.fields.is_empty() {
-            panic!("Deriving enums in scroll must be primitive, fieldless enums");
+            panic!(
+                "Deriving enums in scroll must be primitive, fieldless enums, or tagged with #[scroll(tag = Type)]"
+            );
         }
     }
 }
 
-const VALID_PRIMITIVE_REPRS: &[&'static str] = &[
+const VALID_PRIMITIVE_REPRS: &[&str] = &[
     "i8", "i16", "i32", "i64", "i128", "u8", "u16", "u32", "u64", "u128",
 ];
 
@@ -313,6 +1523,8 @@ fn impl_try_from_ctx_enum(
     name: &syn::Ident,
     repr_type: syn::Ident,
     variants: &syn::punctuated::Punctuated<syn::Variant, syn::token::Comma>,
+    ctx: &proc_macro2::TokenStream,
+    error_ty: &proc_macro2::TokenStream,
 ) -> proc_macro2::TokenStream {
     let variant_consts = variants.iter().map(|variant| {
         let ident = &variant.ident;
@@ -328,21 +1540,18 @@ fn impl_try_from_ctx_enum(
             #const_name => #name::#ident,
         }
     });
-    let static_msg = format!(
-        "No variants matched a discriminant of type {}",
-        repr_type.to_string()
-    );
+    let static_msg = format!("No variants matched a discriminant of type {repr_type}");
     quote! {
      impl<'a> ::scroll::ctx::TryFromCtx<'a, ::scroll::Endian> for #name {
-            type Error = ::scroll::Error;
+            type Error = #error_ty;
             #[inline]
             fn try_from_ctx(src: &'a [u8], ctx: ::scroll::Endian) -> ::scroll::export::result::Result<(Self, usize), Self::Error> {
               use ::scroll::Pread;
               #(#variant_consts)*
               let offset = &mut 0;
-              let val = match src.gread_with::<#repr_type>(offset, ctx)? {
+              let val = match src.gread_with::<#repr_type>(offset, #ctx)? {
                   #(#variant_cases)*
-                  _ => return Err(::scroll::Error::BadInput { size: *offset, msg: #static_msg})
+                  _ => return Err(::core::convert::Into::into(::scroll::Error::BadInput { size: *offset, msg: #static_msg}))
               };
               Ok((val, *offset))
             }
@@ -356,20 +1565,336 @@ fn validate_enum(ast: &syn::DeriveInput, data: &syn::DataEnum) -> Ident {
     repr_type
 }
 
-fn impl_try_from_ctx(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+/// The value of the tag identifying `variant` in a tagged enum: `#[scroll(tag_value = expr)]`, or
+/// else its explicit discriminant
+fn tag_value(variant: &syn::Variant) -> syn::Result<syn::Expr> {
+    let mut value = None;
+    for attr in variant
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("scroll"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag_value") {
+                // parsed #[scroll(tag_value = expr)]
+                value = Some(meta.value()?.parse()?);
+                return Ok(());
+            }
+            Err(meta.error(match meta.path.get_ident() {
+                Some(ident) => format!("unrecognized attribute: {ident}"),
+                None => "unrecognized and invalid attribute".to_owned(),
+            }))
+        })?;
+    }
+    value
+        .or_else(|| variant.discriminant.as_ref().map(|(_, expr)| expr.clone()))
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                variant,
+                "variants of tagged enums need a #[scroll(tag_value = expr)] or a discriminant",
+            )
+        })
+}
+
+/// The names the fields of a variant are bound to, and the pattern binding them
+fn variant_bindings(
+    name: &syn::Ident,
+    variant: &syn::Variant,
+) -> (Vec<proc_macro2::TokenStream>, proc_macro2::TokenStream) {
+    let ident = &variant.ident;
+    let bindings: Vec<_> = variant
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| match &f.ident {
+            Some(ident) => quote! { #ident },
+            None => format_ident!("_{i}").into_token_stream(),
+        })
+        .collect();
+    let pattern = match &variant.fields {
+        syn::Fields::Named(_) => quote! { #name::#ident { #(#bindings),* } },
+        syn::Fields::Unnamed(_) => quote! { #name::#ident ( #(#bindings),* ) },
+        syn::Fields::Unit => quote! { #name::#ident },
+    };
+    (bindings, pattern)
+}
+
+/// Tagged enums only support a lifetime as generic parameter
+fn ensure_no_type_generics(generics: &syn::Generics, derive: &str) {
+    if generics
+        .params
+        .iter()
+        .any(|param| !matches!(param, syn::GenericParam::Lifetime(_)))
+    {
+        panic!("{derive} cannot be derived for tagged enums with generic parameters");
+    }
+}
+
+fn impl_try_from_ctx_tagged_enum(
+    name: &syn::Ident,
+    data: &syn::DataEnum,
+    generics: &syn::Generics,
+    container: &ContainerAttrs,
+) -> proc_macro2::TokenStream {
+    let tag_ty = container
+        .tag
+        .as_ref()
+        .expect("only called for tagged enums");
+    let ctx_ty = &container.ctx_ty();
+    ensure_no_type_generics(generics, "Pread");
+    let fields = data.variants.iter().flat_map(|variant| &variant.fields);
+    let offset = fresh_name(fields.clone(), format_ident!("offset"));
+    let src = fresh_name(fields.clone(), format_ident!("src"));
+    let ctx = fresh_name(fields.clone(), format_ident!("ctx")).to_token_stream();
+    let tag = fresh_name(fields, format_ident!("tag"));
+    let magic = container.impl_magic(&name.to_string(), &src, &offset);
+    let tag_ctx = container.default_ctx(&ctx);
+    let validate = container.impl_validate(&quote! { val });
+    let error_ty = container.error_ty();
+    let offset_error = container.offset_error();
+    let mut arms = Vec::new();
+    for variant in &data.variants {
+        let value = match tag_value(variant) {
+            Ok(value) => value,
+            Err(e) => return e.into_compile_error(),
+        };
+        let type_name = format!("{name}::{}", variant.ident);
+        let (bindings, pattern) = variant_bindings(name, variant);
+        let field_attrs = match FieldAttrs::parse_all(&variant.fields) {
+            Ok(attrs) => attrs,
+            Err(e) => return e.into_compile_error(),
+        };
+        let reads = variant
+            .fields
+            .iter()
+            .zip(&field_attrs)
+            .zip(&bindings)
+            .enumerate()
+            .map(|(i, ((f, attrs), binding))| {
+                let field = f
+                    .ident
+                    .as_ref()
+                    .map(|ident| ident.to_string())
+                    .unwrap_or_else(|| i.to_string());
+                let context = field_context(&type_name, &field, &offset, container);
+                impl_field(
+                    binding,
+                    &f.ty,
+                    &src,
+                    &attrs.default_ctx(container, &ctx),
+                    attrs,
+                    &offset,
+                    &context,
+                )
+            });
+        arms.push(quote! {
+            if #tag == (#value) {
+                #(#reads)*
+                #pattern
+            }
+        });
+    }
+    let static_msg = format!("No variants of {name} matched the tag");
+    let tag_context = field_context(&name.to_string(), "tag", &offset, container);
+    let gp = &generics.params;
+    let (lifetime, _fresh_lifetime) = extract_lifetime(gp);
+    quote! {
+        impl<#lifetime> ::scroll::ctx::TryFromCtx<#lifetime, #ctx_ty> for #name <#gp> {
+            type Error = #error_ty;
+            #offset_error
+            #[inline]
+            fn try_from_ctx(#src: &#lifetime [u8], #ctx: #ctx_ty) -> ::scroll::export::result::Result<(Self, usize), Self::Error> {
+                use ::scroll::Pread;
+                let #offset = &mut 0;
+                #magic
+                let #tag = #src.gread_with::<#tag_ty>(#offset, #tag_ctx).map_err(#tag_context)?;
+                let val = #(#arms else)* {
+                    return Err(::core::convert::Into::into(::scroll::Error::BadInput { size: *#offset, msg: #static_msg }));
+                };
+                #validate
+                Ok((val, *#offset))
+            }
+        }
+    }
+}
+
+fn impl_try_into_ctx_tagged_enum(
+    name: &syn::Ident,
+    data: &syn::DataEnum,
+    generics: &syn::Generics,
+    container: &ContainerAttrs,
+) -> proc_macro2::TokenStream {
+    let tag_ty = container
+        .tag
+        .as_ref()
+        .expect("only called for tagged enums");
+    let ctx_ty = &container.ctx_ty();
+    ensure_no_type_generics(generics, "Pwrite");
+    let fields = data.variants.iter().flat_map(|variant| &variant.fields);
+    let offset = fresh_name(fields.clone(), format_ident!("offset"));
+    let dst = fresh_name(fields.clone(), format_ident!("dst"));
+    let ctx = fresh_name(fields, format_ident!("ctx")).to_token_stream();
+    let magic = container.impl_pwrite_magic(&name.to_string(), &dst, &offset);
+    let tag_ctx = container.default_ctx(&ctx);
+    let error_ty = container.error_ty();
+    let offset_error = container.offset_error();
+    let tag_context = field_context(&name.to_string(), "tag", &offset, container);
+    let mut arms = Vec::new();
+    for variant in &data.variants {
+        let value = match tag_value(variant) {
+            Ok(value) => value,
+            Err(e) => return e.into_compile_error(),
+        };
+        let type_name = format!("{name}::{}", variant.ident);
+        let (bindings, pattern) = variant_bindings(name, variant);
+        let field_attrs = match FieldAttrs::parse_all(&variant.fields) {
+            Ok(attrs) => attrs,
+            Err(e) => return e.into_compile_error(),
+        };
+        // the fields are bound by reference
+        let values: Vec<_> = variant
+            .fields
+            .iter()
+            .zip(&bindings)
+            .filter_map(|(f, binding)| Some((f.ident.as_ref()?, quote! { (*#binding) })))
+            .collect();
+        let writes = variant
+            .fields
+            .iter()
+            .zip(&field_attrs)
+            .zip(&bindings)
+            .enumerate()
+            .map(|(i, ((f, attrs), binding))| {
+                let field = f
+                    .ident
+                    .as_ref()
+                    .map(|ident| ident.to_string())
+                    .unwrap_or_else(|| i.to_string());
+                let context = field_context(&type_name, &field, &offset, container);
+                let value = quote! { (*#binding) };
+                let earlier = &values[..values.len().min(i)];
+                let cond = impl_pwrite_cond(&value, attrs, earlier, &context);
+                let write = impl_pwrite_field(
+                    &value,
+                    &f.ty,
+                    &dst,
+                    &attrs.default_ctx(container, &ctx),
+                    attrs,
+                    &offset,
+                    &context,
+                );
+                quote! { #cond #write }
+            });
+        arms.push(quote! {
+            #pattern => {
+                #dst.gwrite_with::<#tag_ty>(#value, #offset, #tag_ctx).map_err(#tag_context)?;
+                #(#writes;)*
+            }
+        });
+    }
+    let gp = &generics.params;
+    let (_lifetime, fresh_lifetime) = extract_lifetime(gp);
+    quote! {
+        impl<#fresh_lifetime, #gp> ::scroll::ctx::TryIntoCtx<#ctx_ty> for &#fresh_lifetime #name <#gp> {
+            type Error = #error_ty;
+            #offset_error
+            #[inline]
+            fn try_into_ctx(self, #dst: &mut [u8], #ctx: #ctx_ty) -> ::scroll::export::result::Result<usize, Self::Error> {
+                use ::scroll::Pwrite;
+                let #offset = &mut 0;
+                #magic
+                match self {
+                    #(#arms)*
+                }
+                Ok(*#offset)
+            }
+        }
+
+        impl<#gp> ::scroll::ctx::TryIntoCtx<#ctx_ty> for #name <#gp> {
+            type Error = #error_ty;
+            #offset_error
+            #[inline]
+            fn try_into_ctx(self, dst: &mut [u8], ctx: #ctx_ty) -> ::scroll::export::result::Result<usize, Self::Error> {
+                (&self).try_into_ctx(dst, ctx)
+            }
+        }
+    }
+}
+
+/// The size of a tagged enum is the size of its tag and its largest variant
+fn impl_size_with_tagged_enum(
+    name: &syn::Ident,
+    data: &syn::DataEnum,
+    generics: &syn::Generics,
+    container: &ContainerAttrs,
+) -> proc_macro2::TokenStream {
+    let tag_ty = container
+        .tag
+        .as_ref()
+        .expect("only called for tagged enums");
+    let ctx_ty = &container.ctx_ty();
+    ensure_no_type_generics(generics, "SizeWith");
+    let magic = container.magic.as_ref().map(size_with_magic);
+    let tag_ctx = container.default_ctx(&quote! { *ctx });
+    let variants = data.variants.iter().map(|variant| {
+        let items = variant.fields.iter().map(|f| size_with_field(f, container));
+        quote! {
+            {
+                let mut size = tag;
+                #(#items)*
+                size
+            }
+        }
+    });
+    let gp = &generics.params;
+    quote! {
+        impl<#gp> ::scroll::ctx::SizeWith<#ctx_ty> for #name <#gp> {
+            #[inline]
+            fn size_with(ctx: &#ctx_ty) -> usize {
+                let mut size = 0;
+                #magic
+                size += <#tag_ty>::size_with(&#tag_ctx);
+                let tag = size;
+                #( size = ::core::cmp::max(size, #variants); )*
+                size
+            }
+        }
+    }
+}
+
+fn impl_try_from_ctx(ast: &syn::DeriveInput, attrs: &ContainerAttrs) -> proc_macro2::TokenStream {
     let name = &ast.ident;
     let generics = &ast.generics;
     match &ast.data {
+        syn::Data::Struct(data) if attrs.bitfield.is_some() => {
+            match Bitfield::parse(name, &data.fields, generics, attrs) {
+                Ok(bitfield) => impl_try_from_ctx_bitfield(&bitfield, attrs),
+                Err(e) => e.into_compile_error(),
+            }
+        }
         syn::Data::Struct(data) => match &data.fields {
-            syn::Fields::Named(fields) => impl_struct(name, &fields.named, generics, false),
-            syn::Fields::Unnamed(fields) => impl_struct(name, &fields.unnamed, generics, true),
+            syn::Fields::Named(fields) => impl_struct(name, &fields.named, generics, false, attrs),
+            syn::Fields::Unnamed(fields) => {
+                impl_struct(name, &fields.unnamed, generics, true, attrs)
+            }
             _ => {
                 panic!("Pread can not be derived for unit structs")
             }
         },
+        syn::Data::Enum(data) if attrs.tag.is_some() => {
+            impl_try_from_ctx_tagged_enum(name, data, generics, attrs)
+        }
         syn::Data::Enum(data) => {
+            attrs.ensure_endian_ctx("Pread");
             let repr_type = validate_enum(ast, data);
-            impl_try_from_ctx_enum(&ast.ident, repr_type, &data.variants)
+            let ctx = attrs.default_ctx(&quote! { ctx });
+            impl_try_from_ctx_enum(
+                &ast.ident,
+                repr_type,
+                &data.variants,
+                &ctx,
+                &attrs.error_ty(),
+            )
         }
         _ => panic!("Pread can only be derived for structs and primitive enums"),
     }
@@ -378,19 +1903,56 @@ fn impl_try_from_ctx(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
 #[proc_macro_derive(Pread, attributes(scroll))]
 pub fn derive_pread(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast: syn::DeriveInput = syn::parse(input).unwrap();
-    let generated = impl_try_from_ctx(&ast);
+    let attrs = match ContainerAttrs::parse(&ast) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error().into(),
+    };
+    let generated = impl_try_from_ctx(&ast, &attrs);
     generated.into()
 }
 
 fn impl_pwrite_field(
-    ident: &proc_macro2::TokenStream,
+    value: &proc_macro2::TokenStream,
     ty: &syn::Type,
+    dst: &Ident,
     default_ctx: &proc_macro2::TokenStream,
-    custom_ctx: Option<&proc_macro2::TokenStream>,
+    attrs: &FieldAttrs,
     offset: &proc_macro2::Ident,
-    noctx: bool,
+    context: &FieldContext,
 ) -> proc_macro2::TokenStream {
-    let ctx = custom_ctx.unwrap_or(default_ctx);
+    if attrs.has_prelude() {
+        let prelude = impl_pwrite_prelude(attrs, dst, offset, context);
+        let attrs = attrs.without_prelude();
+        let write = impl_pwrite_field(value, ty, dst, default_ctx, &attrs, offset, context);
+        return quote! { #prelude #write };
+    }
+    if attrs.cond.is_some() {
+        // whatever is present is written, `impl_pwrite_cond` checked it against the condition
+        let (ty, attrs) = attrs.present(ty);
+        let write = impl_pwrite_field(
+            &quote! { (*__value) },
+            &ty,
+            dst,
+            default_ctx,
+            &attrs,
+            offset,
+            context,
+        );
+        return quote! {
+            if let ::core::option::Option::Some(__value) = &#value {
+                #write;
+            }
+        };
+    }
+    let ctx = attrs.ctx.as_ref().unwrap_or(default_ctx);
+    if let Some(write_with) = &attrs.write_with {
+        return quote! {
+            #write_with(&#value, #dst, #offset, #ctx).map_err(#context)?
+        };
+    }
+    if let Some(len) = &attrs.len {
+        return impl_pwrite_len_field(value, ty, dst, ctx, len, offset, context);
+    }
     match ty {
         syn::Type::Array(array) => match &array.len {
             syn::Expr::Lit(syn::ExprLit {
@@ -398,45 +1960,136 @@ fn impl_pwrite_field(
                 ..
             }) => {
                 let size = int.base10_parse::<usize>().unwrap();
+                let index_context = context.at_index(quote! { __i });
+                // the offset is still at the start of the failed element, so that is where the
+                // index context puts the error
                 quote! {
-                    for i in 0..#size {
-                        dst.gwrite_with(&self.#ident[i], #offset, #ctx)?;
+                    for __i in 0..#size {
+                        #dst.gwrite_with(&#value[__i], #offset, #ctx)
+                            .map_err(#index_context)
+                            .map_err(#context)?;
                     }
                 }
             }
             _ => panic!("Pwrite derive with bad array constexpr"),
         },
         syn::Type::Group(group) => {
-            impl_pwrite_field(ident, &group.elem, default_ctx, custom_ctx, offset, noctx)
+            impl_pwrite_field(value, &group.elem, dst, default_ctx, attrs, offset, context)
         }
         syn::Type::Reference(reference) => match *reference.elem {
             syn::Type::Slice(_) => {
                 quote! {
-                    dst.gwrite_with(self.#ident, #offset, ())?
+                    #dst.gwrite_with(#value, #offset, ()).map_err(#context)?
                 }
             }
-            syn::Type::Path(ref path) => {
-                if path.path.get_ident().unwrap().to_string().as_str() == "str" {
-                    quote! {
-                        dst.gwrite(self.#ident, #offset)?
-                    }
-                } else {
-                    quote! {
-                        dst.gwrite_with(self.#ident, #offset, #ctx)?
-                    }
+            syn::Type::Path(ref path) if path.path.is_ident("str") => {
+                quote! {
+                    #dst.gwrite(#value, #offset).map_err(#context)?
                 }
             }
             _ => {
                 quote! {
-                    dst.gwrite_with(self.#ident, #offset, #ctx)?
+                    #dst.gwrite_with(#value, #offset, #ctx).map_err(#context)?
                 }
             }
         },
         _ => {
             quote! {
-                dst.gwrite_with(&self.#ident, #offset, #ctx)?
+                #dst.gwrite_with(&#value, #offset, #ctx).map_err(#context)?
+            }
+        }
+    }
+}
+
+/// Checks that an `Option<T>` field with `#[scroll(if = expr)]` is present exactly when `expr`
+/// holds, since a reader wouldn't read it back otherwise. The earlier fields named by `expr` are
+/// bound to clones of their values, like the locals they are while reading.
+fn impl_pwrite_cond(
+    value: &proc_macro2::TokenStream,
+    attrs: &FieldAttrs,
+    earlier: &[(&Ident, proc_macro2::TokenStream)],
+    context: &FieldContext,
+) -> proc_macro2::TokenStream {
+    let Some(cond) = &attrs.cond else {
+        return quote! {};
+    };
+    let msg = format!("presence doesn't match: {}", cond.to_token_stream());
+    let cond = strip_self(cond.to_token_stream());
+    let mut named = Vec::new();
+    collect_idents(cond.clone(), &mut named);
+    let bindings = earlier
+        .iter()
+        .filter(|(ident, _)| named.contains(*ident))
+        .map(|(ident, value)| {
+            quote! {
+                #[allow(unused_variables)]
+                let #ident = ::core::clone::Clone::clone(&#value);
+            }
+        });
+    quote! {
+        if { #(#bindings)* #cond } != ::core::option::Option::is_some(&#value) {
+            return Err(::scroll::Error::BadInput { size: 0, msg: #msg }).map_err(#context);
+        }
+    }
+}
+
+fn collect_idents(tokens: proc_macro2::TokenStream, idents: &mut Vec<Ident>) {
+    for token in tokens {
+        match token {
+            proc_macro2::TokenTree::Ident(ident) => idents.push(ident),
+            proc_macro2::TokenTree::Group(group) => collect_idents(group.stream(), idents),
+            _ => {}
+        }
+    }
+}
+
+/// Writes a `Vec<T>`, `&[u8]` or `&str` field whose length is given by `len`; only a length
+/// prefix is written in addition to the elements, a count is up to the user to keep in sync
+fn impl_pwrite_len_field(
+    value: &proc_macro2::TokenStream,
+    ty: &syn::Type,
+    dst: &Ident,
+    ctx: &proc_macro2::TokenStream,
+    len: &FieldLen,
+    offset: &proc_macro2::Ident,
+    context: &FieldContext,
+) -> proc_macro2::TokenStream {
+    let collection = Collection::of(ty).expect("checked while parsing the attributes");
+    let prefix = match len {
+        FieldLen::Prefix(prefix) => quote! {
+            let __len: #prefix = ::core::convert::TryFrom::try_from(#value.len())
+                .map_err(|_| ::scroll::Error::BadInput {
+                    size: #value.len(),
+                    msg: "length doesn't fit in the length prefix",
+                })
+                .map_err(#context)?;
+            #dst.gwrite_with(__len, #offset, #ctx).map_err(#context)?;
+        },
+        _ => quote! {},
+    };
+    let elements = match collection {
+        Collection::Bytes => quote! {
+            #dst.gwrite_with(#value, #offset, ()).map_err(#context)?;
+        },
+        Collection::Str => quote! {
+            #dst.gwrite(#value, #offset).map_err(#context)?;
+        },
+        Collection::Vec(_) => {
+            let index_context = context.at_index(quote! { __i });
+            quote! {
+            for (__i, __element) in #value.iter().enumerate() {
+                #dst.gwrite_with(__element, #offset, #ctx)
+                    .map_err(#index_context)
+                    .map_err(#context)?;
+            }
             }
         }
+    };
+    quote! {
+        {
+            #prefix
+            #elements
+        }
     }
 }
 
@@ -444,20 +2097,49 @@ fn impl_try_into_ctx(
     name: &syn::Ident,
     fields: &syn::punctuated::Punctuated<syn::Field, syn::Token![,]>,
     generics: &syn::Generics,
+    container: &ContainerAttrs,
 ) -> proc_macro2::TokenStream {
+    let ctx_ty = &container.ctx_ty();
     let (idents, offset) = extract_idents_and_offset(fields);
     let ctx = fresh_name(
         fields,
         syn::Ident::new("ctx", proc_macro2::Span::call_site()),
     )
     .to_token_stream();
+    // fields are only accessed through `self`, so they can't clash with `dst`
+    let dst = syn::Ident::new("dst", proc_macro2::Span::call_site());
+    let magic = container.impl_pwrite_magic(&name.to_string(), &dst, &offset);
+    let field_attrs = match FieldAttrs::parse_all(fields) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error(),
+    };
+    let values: Vec<_> = fields
+        .iter()
+        .filter_map(|f| {
+            let ident = f.ident.as_ref()?;
+            Some((ident, quote! { self.#ident }))
+        })
+        .collect();
     let items: Vec<_> = idents
         .iter()
-        .map(|(ident, f)| {
+        .zip(&field_attrs)
+        .enumerate()
+        .map(|(i, ((ident, f), attrs))| {
             let ty = &f.ty;
-            let mut noctx = false;
-            let custom_ctx = custom_ctx(f, &mut noctx);
-            impl_pwrite_field(ident, ty, &ctx, custom_ctx.as_ref(), &offset, noctx)
+            let context = field_context(&name.to_string(), &ident.to_string(), &offset, container);
+            let value = quote! { self.#ident };
+            let earlier = &values[..values.len().min(i)];
+            let cond = impl_pwrite_cond(&value, attrs, earlier, &context);
+            let write = impl_pwrite_field(
+                &value,
+                ty,
+                &dst,
+                &attrs.default_ctx(container, &ctx),
+                attrs,
+                &offset,
+                &context,
+            );
+            quote! { #cond #write }
         })
         .collect();
 
@@ -474,14 +2156,16 @@ fn impl_try_into_ctx(
     let gn = quote! { #gl #( #gn ),* #gg };
     // it's always important to keep it _fresh_ when we pwrite
     let (_lifetime, fresh_lifetime) = extract_lifetime(gp);
+    let error_ty = container.error_ty();
+    let offset_error = container.offset_error();
     let gwref = if !gp.is_empty() {
         let gi: Vec<_> = gp.iter().filter_map(|param: &syn::GenericParam| match param {
             syn::GenericParam::Type(t) => {
                 let ident = &t.ident;
                 Some(quote! {
-                    &#fresh_lifetime #ident : ::scroll::ctx::TryIntoCtx<::scroll::Endian>,
-                    ::scroll::Error: ::std::convert::From<<&#fresh_lifetime #ident as ::scroll::ctx::TryIntoCtx<::scroll::Endian>>::Error>,
-                    <&#fresh_lifetime #ident as ::scroll::ctx::TryIntoCtx<::scroll::Endian>>::Error: ::std::convert::From<scroll::Error>
+                    &#fresh_lifetime #ident : ::scroll::ctx::TryIntoCtx<#ctx_ty>,
+                    #error_ty: ::std::convert::From<<&#fresh_lifetime #ident as ::scroll::ctx::TryIntoCtx<#ctx_ty>>::Error>,
+                    <&#fresh_lifetime #ident as ::scroll::ctx::TryIntoCtx<#ctx_ty>>::Error: ::std::convert::From<scroll::Error>
                 })
             },
             syn::GenericParam::Lifetime(_) => None,
@@ -500,9 +2184,9 @@ fn impl_try_into_ctx(
             syn::GenericParam::Type(t) => {
                 let ident = &t.ident;
                 Some(quote! {
-                    #ident : ::scroll::ctx::TryIntoCtx<::scroll::Endian>,
-                    ::scroll::Error: ::std::convert::From<<#ident as ::scroll::ctx::TryIntoCtx<::scroll::Endian>>::Error>,
-                    <#ident as ::scroll::ctx::TryIntoCtx<::scroll::Endian>>::Error: ::std::convert::From<scroll::Error>
+                    #ident : ::scroll::ctx::TryIntoCtx<#ctx_ty>,
+                    #error_ty: ::std::convert::From<<#ident as ::scroll::ctx::TryIntoCtx<#ctx_ty>>::Error>,
+                    <#ident as ::scroll::ctx::TryIntoCtx<#ctx_ty>>::Error: ::std::convert::From<scroll::Error>
                 })
             },
             syn::GenericParam::Lifetime(_) => None,
@@ -514,21 +2198,24 @@ fn impl_try_into_ctx(
     };
 
     quote! {
-        impl<#fresh_lifetime, #gp > ::scroll::ctx::TryIntoCtx<::scroll::Endian> for &#fresh_lifetime #name #gn #gwref {
-            type Error = ::scroll::Error;
+        impl<#fresh_lifetime, #gp > ::scroll::ctx::TryIntoCtx<#ctx_ty> for &#fresh_lifetime #name #gn #gwref {
+            type Error = #error_ty;
+            #offset_error
             #[inline]
-            fn try_into_ctx(self, dst: &mut [u8], #ctx: ::scroll::Endian) -> ::scroll::export::result::Result<usize, Self::Error> {
+            fn try_into_ctx(self, dst: &mut [u8], #ctx: #ctx_ty) -> ::scroll::export::result::Result<usize, Self::Error> {
                 use ::scroll::Pwrite;
                 let #offset = &mut 0;
+                #magic
                 #(#items;)*
                 Ok(*#offset)
             }
         }
 
-        impl #gl #gp #gg ::scroll::ctx::TryIntoCtx<::scroll::Endian> for #name #gn #gw {
-            type Error = ::scroll::Error;
+        impl #gl #gp #gg ::scroll::ctx::TryIntoCtx<#ctx_ty> for #name #gn #gw {
+            type Error = #error_ty;
+            #offset_error
             #[inline]
-            fn try_into_ctx(self, dst: &mut [u8], ctx: ::scroll::Endian) -> ::scroll::export::result::Result<usize, Self::Error> {
+            fn try_into_ctx(self, dst: &mut [u8], ctx: #ctx_ty) -> ::scroll::export::result::Result<usize, Self::Error> {
                 (&self).try_into_ctx(dst, ctx)
             }
         }
@@ -539,10 +2226,12 @@ fn impl_try_into_ctx_primitive_enum(
     name: &Ident,
     repr_type: Ident,
     _variants: &syn::punctuated::Punctuated<syn::Variant, syn::token::Comma>,
+    ctx: &proc_macro2::TokenStream,
+    error_ty: &proc_macro2::TokenStream,
 ) -> proc_macro2::TokenStream {
     quote! {
         impl ::scroll::ctx::TryIntoCtx<::scroll::Endian> for &'_ #name {
-            type Error = ::scroll::Error;
+            type Error = #error_ty;
             #[inline]
             fn try_into_ctx(self, dst: &mut [u8], ctx: ::scroll::Endian) -> ::scroll::export::result::Result<usize, Self::Error> {
                 use ::scroll::Pwrite;
@@ -550,12 +2239,13 @@ fn impl_try_into_ctx_primitive_enum(
                 // > If an enum has opted-in to having a primitive representation for its discriminant,
                 // > then it’s possible to use pointers to read the memory location storing the discriminant.
                 // NB: the derive macro ensures that we are a primitive (and also fieldless) enum
-                dst.pwrite_with(unsafe { *<*const _>::from(self).cast::<#repr_type>() }, 0, ctx)
+                dst.pwrite_with(unsafe { *<*const _>::from(self).cast::<#repr_type>() }, 0, #ctx)
+                    .map_err(::core::convert::Into::<Self::Error>::into)
             }
         }
 
         impl ::scroll::ctx::TryIntoCtx<::scroll::Endian> for #name {
-            type Error = ::scroll::Error;
+            type Error = #error_ty;
             #[inline]
             fn try_into_ctx(self, dst: &mut [u8], ctx: ::scroll::Endian) -> ::scroll::export::result::Result<usize, Self::Error> {
                 (&self).try_into_ctx(dst, ctx)
@@ -564,20 +2254,39 @@ fn impl_try_into_ctx_primitive_enum(
     }
 }
 
-fn impl_pwrite(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+fn impl_pwrite(ast: &syn::DeriveInput, attrs: &ContainerAttrs) -> proc_macro2::TokenStream {
     let name = &ast.ident;
     let generics = &ast.generics;
     match &ast.data {
+        syn::Data::Struct(data) if attrs.bitfield.is_some() => {
+            match Bitfield::parse(name, &data.fields, generics, attrs) {
+                Ok(bitfield) => impl_try_into_ctx_bitfield(&bitfield, attrs),
+                Err(e) => e.into_compile_error(),
+            }
+        }
         syn::Data::Struct(data) => match &data.fields {
-            syn::Fields::Named(fields) => impl_try_into_ctx(name, &fields.named, generics),
-            syn::Fields::Unnamed(fields) => impl_try_into_ctx(name, &fields.unnamed, generics),
+            syn::Fields::Named(fields) => impl_try_into_ctx(name, &fields.named, generics, attrs),
+            syn::Fields::Unnamed(fields) => {
+                impl_try_into_ctx(name, &fields.unnamed, generics, attrs)
+            }
             _ => {
                 panic!("Pwrite can not be derived for unit structs")
             }
         },
+        syn::Data::Enum(data) if attrs.tag.is_some() => {
+            impl_try_into_ctx_tagged_enum(name, data, generics, attrs)
+        }
         syn::Data::Enum(data) => {
+            attrs.ensure_endian_ctx("Pwrite");
             let repr_type = validate_enum(ast, data);
-            impl_try_into_ctx_primitive_enum(&ast.ident, repr_type, &data.variants)
+            let ctx = attrs.default_ctx(&quote! { ctx });
+            impl_try_into_ctx_primitive_enum(
+                &ast.ident,
+                repr_type,
+                &data.variants,
+                &ctx,
+                &attrs.error_ty(),
+            )
         }
         _ => panic!("Pwrite can only be derived for structs and primitive enums"),
     }
@@ -586,50 +2295,106 @@ fn impl_pwrite(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
 #[proc_macro_derive(Pwrite, attributes(scroll))]
 pub fn derive_pwrite(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast: syn::DeriveInput = syn::parse(input).unwrap();
-    let generated = impl_pwrite(&ast);
+    let attrs = match ContainerAttrs::parse(&ast) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error().into(),
+    };
+    let generated = impl_pwrite(&ast, &attrs);
     generated.into()
 }
 
+/// Adds the size of field `f` to `size` in a derived `SizeWith`, in terms of `ctx`; `size` is the
+/// offset of the field from the start of the type, as alignment depends on it
+fn size_with_field(f: &syn::Field, container: &ContainerAttrs) -> proc_macro2::TokenStream {
+    let attrs = match FieldAttrs::parse(f) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error(),
+    };
+    let pad = attrs.pad.as_ref().map(|pad| quote! { size += #pad; });
+    let align = attrs
+        .align
+        .as_ref()
+        .map(|align| quote! { size = size.next_multiple_of(#align); });
+    let magic = attrs.magic.as_ref().map(size_with_magic);
+    // the condition can depend on earlier fields, so an optional field counts as present, like the
+    // largest variant of a tagged enum
+    let (ty, attrs) = match attrs.cond {
+        Some(_) => attrs.present(&f.ty),
+        None => (f.ty.clone(), attrs),
+    };
+    let ty = &ty;
+    if attrs.len.is_some() && attrs.size_with.is_none() {
+        panic!("SizeWith cannot be derived for fields with a variable length")
+    }
+    let ctx = match attrs.ctx {
+        Some(x) => quote! {&#x},
+        None => {
+            let ctx = attrs.default_ctx(container, &quote! { *ctx });
+            quote! {&#ctx}
+        }
+    };
+    if let Some(size_with) = &attrs.size_with {
+        return quote! {
+            #pad
+            #align
+            #magic
+            size += #size_with(#ctx);
+        };
+    }
+    let field = match ty {
+        syn::Type::Reference(_) => {
+            panic!("SizeWith cannot be derived for references")
+        }
+        syn::Type::Array(array) => {
+            let elem = &array.elem;
+            match &array.len {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Int(int),
+                    ..
+                }) => {
+                    let size = int.base10_parse::<usize>().unwrap();
+                    quote! {
+                        (#size * <#elem>::size_with(#ctx))
+                    }
+                }
+                _ => panic!("SizeWith derive has bad array constexpr"),
+            }
+        }
+        _ => {
+            quote! {
+                <#ty>::size_with(#ctx)
+            }
+        }
+    };
+    quote! {
+        #pad
+        #align
+        #magic
+        size += #field;
+    }
+}
+
+/// Adds the size of a magic to `size`
+fn size_with_magic(magic: &syn::Expr) -> proc_macro2::TokenStream {
+    quote! {
+        size += {
+            let __magic: &[u8] = #magic;
+            __magic.len()
+        };
+    }
+}
+
 fn size_with(
     name: &syn::Ident,
     fields: &syn::punctuated::Punctuated<syn::Field, syn::Token![,]>,
     generics: &syn::Generics,
+    container: &ContainerAttrs,
 ) -> proc_macro2::TokenStream {
+    let ctx_ty = &container.ctx_ty();
+    let magic = container.magic.as_ref().map(size_with_magic);
     let items: Vec<_> = fields
         .iter()
-        .map(|f| {
-            let ty = &f.ty;
-            let mut noctx = false;
-            let custom_ctx = custom_ctx(f, &mut noctx).map(|x| quote! {&#x});
-            let default_ctx =
-                syn::Ident::new("ctx", proc_macro2::Span::call_site()).into_token_stream();
-            let ctx = custom_ctx.unwrap_or(default_ctx);
-            match ty {
-                syn::Type::Reference(_) => {
-                    panic!("SizeWith cannot be derived for references")
-                }
-                syn::Type::Array(array) => {
-                    let elem = &array.elem;
-                    match &array.len {
-                        syn::Expr::Lit(syn::ExprLit {
-                            lit: syn::Lit::Int(int),
-                            ..
-                        }) => {
-                            let size = int.base10_parse::<usize>().unwrap();
-                            quote! {
-                                (#size * <#elem>::size_with(#ctx))
-                            }
-                        }
-                        _ => panic!("SizeWith derive has bad array constexpr"),
-                    }
-                }
-                _ => {
-                    quote! {
-                        <#ty>::size_with(#ctx)
-                    }
-                }
-            }
-        })
+        .map(|f| size_with_field(f, container))
         .collect();
 
     let gl = &generics.lt_token;
@@ -650,7 +2415,7 @@ fn size_with(
                 syn::GenericParam::Type(t) => {
                     let ident = &t.ident;
                     Some(quote! {
-                        #ident : ::scroll::ctx::SizeWith<::scroll::Endian>
+                        #ident : ::scroll::ctx::SizeWith<#ctx_ty>
                     })
                 }
                 syn::GenericParam::Lifetime(_) => None,
@@ -662,34 +2427,50 @@ fn size_with(
     };
 
     quote! {
-        impl #gl #gp #gg ::scroll::ctx::SizeWith<::scroll::Endian> for #name #gn #gw {
+        impl #gl #gp #gg ::scroll::ctx::SizeWith<#ctx_ty> for #name #gn #gw {
             #[inline]
-            fn size_with(ctx: &::scroll::Endian) -> usize {
-                0 #(+ #items)*
+            fn size_with(ctx: &#ctx_ty) -> usize {
+                let mut size = 0;
+                #magic
+                #(#items)*
+                size
             }
         }
     }
 }
 
-fn impl_size_with(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+fn impl_size_with(ast: &syn::DeriveInput, attrs: &ContainerAttrs) -> proc_macro2::TokenStream {
     let name = &ast.ident;
     let generics = &ast.generics;
     match &ast.data {
+        syn::Data::Struct(data) if attrs.bitfield.is_some() => {
+            match Bitfield::parse(name, &data.fields, generics, attrs) {
+                Ok(bitfield) => impl_size_with_bitfield(&bitfield),
+                Err(e) => e.into_compile_error(),
+            }
+        }
         syn::Data::Struct(data) => match &data.fields {
-            syn::Fields::Named(fields) => size_with(name, &fields.named, generics),
-            syn::Fields::Unnamed(fields) => size_with(name, &fields.unnamed, generics),
+            syn::Fields::Named(fields) => size_with(name, &fields.named, generics, attrs),
+            syn::Fields::Unnamed(fields) => size_with(name, &fields.unnamed, generics, attrs),
             _ => {
                 panic!("SizeWith can not be derived for unit structs")
             }
         },
-        _ => panic!("SizeWith can only be derived for structs"),
+        syn::Data::Enum(data) if attrs.tag.is_some() => {
+            impl_size_with_tagged_enum(name, data, generics, attrs)
+        }
+        _ => panic!("SizeWith can only be derived for structs and tagged enums"),
     }
 }
 
 #[proc_macro_derive(SizeWith, attributes(scroll))]
 pub fn derive_sizewith(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast: syn::DeriveInput = syn::parse(input).unwrap();
-    let generated = impl_size_with(&ast);
+    let attrs = match ContainerAttrs::parse(&ast) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error().into(),
+    };
+    let generated = impl_size_with(&ast, &attrs);
     generated.into()
 }
 
@@ -697,16 +2478,27 @@ fn impl_cread_struct(
     name: &syn::Ident,
     fields: &syn::punctuated::Punctuated<syn::Field, syn::Token![,]>,
     generics: &syn::Generics,
+    container: &ContainerAttrs,
 ) -> proc_macro2::TokenStream {
-    let items: Vec<_> = fields.iter().enumerate().map(|(i, f)| {
+    let ctx_ty = &container.ctx_ty();
+    let field_attrs = match FieldAttrs::parse_all(fields).and_then(|attrs| {
+        container
+            .ensure_infallible(fields, &attrs, "IOread")
+            .map(|()| attrs)
+    }) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error(),
+    };
+    let items: Vec<_> = fields.iter().zip(field_attrs).enumerate().map(|(i, (f, attrs))| {
         let ident = &f.ident.as_ref().map(|i|quote!{#i}).unwrap_or({let t = proc_macro2::Literal::usize_unsuffixed(i); quote!{#t}});
         let ty = &f.ty;
-        let mut noctx = false;
-        let custom_ctx = custom_ctx(f, &mut noctx);
-        let default_ctx =
-            syn::Ident::new("ctx", proc_macro2::Span::call_site()).into_token_stream();
-        let ctx = custom_ctx.unwrap_or(default_ctx);
-        match ty {
+        let default_ctx = attrs.default_ctx(container, &quote! { ctx });
+        let pad = attrs.pad.as_ref().map(|pad| quote! { *offset += #pad; });
+        let align = attrs.align.as_ref().map(|align| {
+            quote! { *offset += (#align - *offset % #align) % #align; }
+        });
+        let ctx = attrs.ctx.unwrap_or(default_ctx);
+        let read = match ty {
             syn::Type::Reference(_) => {
                 panic!("IOread cannot be derived for references, because SizeWith cannot be derived for references")
             }
@@ -717,14 +2509,12 @@ fn impl_cread_struct(
                         let size = int.base10_parse::<usize>().unwrap();
                         let incr = quote! { ::scroll::export::mem::size_of::<#arrty>() };
                         quote! {
-                            #ident: {
-                                let mut __tmp: #ty = [0u8.into(); #size];
-                                for i in 0..__tmp.len() {
-                                    __tmp[i] = src.cread_with(*offset, #ctx);
-                                    *offset += #incr;
-                                }
-                                __tmp
+                            let mut __tmp: #ty = [0u8.into(); #size];
+                            for __i in 0..__tmp.len() {
+                                __tmp[__i] = src.cread_with(*offset, #ctx);
+                                *offset += #incr;
                             }
+                            __tmp
                         }
                     },
                     _ => panic!("IOread derive with bad array constexpr")
@@ -733,9 +2523,13 @@ fn impl_cread_struct(
             _ => {
                 let size = quote! { ::scroll::export::mem::size_of::<#ty>() };
                 quote! {
-                    #ident: { let res = src.cread_with::<#ty>(*offset, #ctx); *offset += #size; res }
+                    let res = src.cread_with::<#ty>(*offset, #ctx); *offset += #size; res
                 }
             }
+        };
+        // the skipped bytes of the padding and alignment are counted by `SizeWith`
+        quote! {
+            #ident: { #pad #align #read }
         }
     }).collect();
 
@@ -755,7 +2549,7 @@ fn impl_cread_struct(
             syn::GenericParam::Type(t) => {
                 let ident = &t.ident;
                 quote! {
-                    #ident : ::scroll::ctx::FromCtx<::scroll::Endian> + ::std::convert::From<u8> + ::std::marker::Copy
+                    #ident : ::scroll::ctx::FromCtx<#ctx_ty> + ::std::convert::From<u8> + ::std::marker::Copy
                 }
             },
             p => quote! { #p }
@@ -766,9 +2560,9 @@ fn impl_cread_struct(
     };
 
     quote! {
-        impl #gl #gp #gg ::scroll::ctx::FromCtx<::scroll::Endian> for #name #gn #gw {
+        impl #gl #gp #gg ::scroll::ctx::FromCtx<#ctx_ty> for #name #gn #gw {
             #[inline]
-            fn from_ctx(src: &[u8], ctx: ::scroll::Endian) -> Self {
+            fn from_ctx(src: &[u8], ctx: #ctx_ty) -> Self {
                 use ::scroll::Cread;
                 let offset = &mut 0;
                 let data = Self { #(#items,)* };
@@ -778,13 +2572,21 @@ fn impl_cread_struct(
     }
 }
 
-fn impl_from_ctx(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+fn impl_from_ctx(ast: &syn::DeriveInput, attrs: &ContainerAttrs) -> proc_macro2::TokenStream {
     let name = &ast.ident;
     let generics = &ast.generics;
     match &ast.data {
+        syn::Data::Struct(data) if attrs.bitfield.is_some() => {
+            match Bitfield::parse(name, &data.fields, generics, attrs) {
+                Ok(bitfield) => impl_from_ctx_bitfield(&bitfield, attrs),
+                Err(e) => e.into_compile_error(),
+            }
+        }
         syn::Data::Struct(data) => match &data.fields {
-            syn::Fields::Named(fields) => impl_cread_struct(name, &fields.named, generics),
-            syn::Fields::Unnamed(fields) => impl_cread_struct(name, &fields.unnamed, generics),
+            syn::Fields::Named(fields) => impl_cread_struct(name, &fields.named, generics, attrs),
+            syn::Fields::Unnamed(fields) => {
+                impl_cread_struct(name, &fields.unnamed, generics, attrs)
+            }
             _ => {
                 panic!("IOread can not be derived for unit structs")
             }
@@ -793,10 +2595,39 @@ fn impl_from_ctx(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
     }
 }
 
+/// Derives `FromCtx` for a struct of fixed size, which `IOread` reads after `SizeWith`.
+///
+/// Padding and alignment are skipped. `FromCtx` can't fail, so the attributes which check the
+/// input, make the size depend on it or read fields with functions which could fail, like a
+/// magic, an assert or `read_with`, are compile errors:
+///
+/// ```compile_fail
+/// use scroll_derive::{IOread, SizeWith};
+///
+/// #[derive(IOread, SizeWith)]
+/// #[scroll(magic = b"MZ")]
+/// struct Header {
+///     size: u32,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use scroll_derive::{IOread, SizeWith};
+///
+/// #[derive(IOread, SizeWith)]
+/// struct Header {
+///     #[scroll(assert = size > 0)]
+///     size: u32,
+/// }
+/// ```
 #[proc_macro_derive(IOread, attributes(scroll))]
 pub fn derive_ioread(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast: syn::DeriveInput = syn::parse(input).unwrap();
-    let generated = impl_from_ctx(&ast);
+    let attrs = match ContainerAttrs::parse(&ast) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error().into(),
+    };
+    let generated = impl_from_ctx(&ast, &attrs);
     generated.into()
 }
 
@@ -804,23 +2635,44 @@ fn impl_into_ctx(
     name: &syn::Ident,
     fields: &syn::punctuated::Punctuated<syn::Field, syn::Token![,]>,
     generics: &syn::Generics,
+    container: &ContainerAttrs,
 ) -> proc_macro2::TokenStream {
+    let ctx_ty = &container.ctx_ty();
+    let field_attrs = match FieldAttrs::parse_all(fields).and_then(|attrs| {
+        container
+            .ensure_infallible(fields, &attrs, "IOwrite")
+            .map(|()| attrs)
+    }) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error(),
+    };
     let items: Vec<_> = fields
         .iter()
+        .zip(field_attrs)
         .enumerate()
-        .map(|(i, f)| {
+        .map(|(i, (f, attrs))| {
             let ident = &f.ident.as_ref().map(|i| quote! {#i}).unwrap_or({
                 let t = proc_macro2::Literal::usize_unsuffixed(i);
                 quote! {#t}
             });
             let ty = &f.ty;
-            let mut noctx = false;
             let size = quote! { ::scroll::export::mem::size_of::<#ty>() };
-            let custom_ctx = custom_ctx(f, &mut noctx);
-            let default_ctx =
-                syn::Ident::new("ctx", proc_macro2::Span::call_site()).into_token_stream();
-            let ctx = custom_ctx.unwrap_or(default_ctx);
-            match ty {
+            let default_ctx = attrs.default_ctx(container, &quote! { ctx });
+            let pad = attrs.pad.as_ref().map(|pad| {
+                quote! {
+                    dst[*offset..*offset + #pad].fill(0);
+                    *offset += #pad;
+                }
+            });
+            let align = attrs.align.as_ref().map(|align| {
+                quote! {
+                    let __size = (#align - *offset % #align) % #align;
+                    dst[*offset..*offset + __size].fill(0);
+                    *offset += __size;
+                }
+            });
+            let ctx = attrs.ctx.unwrap_or(default_ctx);
+            let write = match ty {
                 syn::Type::Reference(_) => {
                     panic!("IOwrite cannot be derived for references, because SizeWith cannot be derived for references")
                 }
@@ -828,8 +2680,8 @@ fn impl_into_ctx(
                     let arrty = &array.elem;
                     quote! {
                         let size = ::scroll::export::mem::size_of::<#arrty>();
-                        for i in 0..self.#ident.len() {
-                            dst.cwrite_with(self.#ident[i], *offset, #ctx);
+                        for __i in 0..self.#ident.len() {
+                            dst.cwrite_with(self.#ident[__i], *offset, #ctx);
                             *offset += size;
                         }
                     }
@@ -840,7 +2692,8 @@ fn impl_into_ctx(
                         *offset += #size;
                     }
                 }
-            }
+            };
+            quote! { #pad #align #write }
         })
         .collect();
 
@@ -859,7 +2712,7 @@ fn impl_into_ctx(
             syn::GenericParam::Type(t) => {
                 let ident = &t.ident;
                 quote! {
-                    #ident : ::scroll::ctx::IntoCtx<::scroll::Endian> + ::std::marker::Copy
+                    #ident : ::scroll::ctx::IntoCtx<#ctx_ty> + ::std::marker::Copy
                 }
             }
             p => quote! { #p },
@@ -871,31 +2724,37 @@ fn impl_into_ctx(
     let gn = quote! { #gl #( #gn ),* #gg };
 
     quote! {
-        impl<'a, #gp > ::scroll::ctx::IntoCtx<::scroll::Endian> for &'a #name #gn #gw {
+        impl<'a, #gp > ::scroll::ctx::IntoCtx<#ctx_ty> for &'a #name #gn #gw {
             #[inline]
-            fn into_ctx(self, dst: &mut [u8], ctx: ::scroll::Endian) {
+            fn into_ctx(self, dst: &mut [u8], ctx: #ctx_ty) {
                 use ::scroll::Cwrite;
                 let offset = &mut 0;
                 #(#items;)*;
             }
         }
 
-        impl #gl #gp #gg ::scroll::ctx::IntoCtx<::scroll::Endian> for #name #gn #gw {
+        impl #gl #gp #gg ::scroll::ctx::IntoCtx<#ctx_ty> for #name #gn #gw {
             #[inline]
-            fn into_ctx(self, dst: &mut [u8], ctx: ::scroll::Endian) {
+            fn into_ctx(self, dst: &mut [u8], ctx: #ctx_ty) {
                 (&self).into_ctx(dst, ctx)
             }
         }
     }
 }
 
-fn impl_iowrite(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+fn impl_iowrite(ast: &syn::DeriveInput, attrs: &ContainerAttrs) -> proc_macro2::TokenStream {
     let name = &ast.ident;
     let generics = &ast.generics;
     match &ast.data {
+        syn::Data::Struct(data) if attrs.bitfield.is_some() => {
+            match Bitfield::parse(name, &data.fields, generics, attrs) {
+                Ok(bitfield) => impl_into_ctx_bitfield(&bitfield, attrs),
+                Err(e) => e.into_compile_error(),
+            }
+        }
         syn::Data::Struct(data) => match &data.fields {
-            syn::Fields::Named(fields) => impl_into_ctx(name, &fields.named, generics),
-            syn::Fields::Unnamed(fields) => impl_into_ctx(name, &fields.unnamed, generics),
+            syn::Fields::Named(fields) => impl_into_ctx(name, &fields.named, generics, attrs),
+            syn::Fields::Unnamed(fields) => impl_into_ctx(name, &fields.unnamed, generics, attrs),
             _ => {
                 panic!("IOwrite can not be derived for unit structs")
             }
@@ -904,9 +2763,42 @@ fn impl_iowrite(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
     }
 }
 
+/// Derives `IntoCtx` for a struct of fixed size, which `IOwrite` writes after `SizeWith`.
+///
+/// Padding and alignment are zeroed. The attributes `IOread` rejects are rejected here too, so
+/// that nothing is written which couldn't be read back:
+///
+/// ```compile_fail
+/// use scroll_derive::{IOwrite, SizeWith};
+///
+/// #[derive(IOwrite, SizeWith)]
+/// struct Header {
+///     #[scroll(magic = b"MZ")]
+///     size: u32,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use scroll_derive::{IOwrite, SizeWith};
+///
+/// fn write_size(size: &u32, dst: &mut [u8], offset: &mut usize, _: scroll::Endian) -> scroll::Result<()> {
+///     *offset += scroll::Pwrite::pwrite(dst, *size, *offset)?;
+///     Ok(())
+/// }
+///
+/// #[derive(IOwrite, SizeWith)]
+/// struct Header {
+///     #[scroll(write_with = write_size)]
+///     size: u32,
+/// }
+/// ```
 #[proc_macro_derive(IOwrite, attributes(scroll))]
 pub fn derive_iowrite(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast: syn::DeriveInput = syn::parse(input).unwrap();
-    let generated = impl_iowrite(&ast);
+    let attrs = match ContainerAttrs::parse(&ast) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error().into(),
+    };
+    let generated = impl_iowrite(&ast, &attrs);
     generated.into()
 }
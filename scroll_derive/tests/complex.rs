@@ -1,3 +1,5 @@
+#![allow(clippy::disallowed_names)]
+
 use scroll::Pread;
 use scroll_derive::{Pread, Pwrite};
 
@@ -351,3 +351,956 @@ fn test_roundtrip_enum_u32() {
     assert_eq!(bytes, [10, 0, 0, 0]);
     assert!(bytes.pwrite(Foo::Three, 1).is_err());
 }
+
+#[derive(Debug, PartialEq, Pread, Pwrite)]
+struct Section {
+    offset: u32,
+    size: u16,
+}
+
+#[derive(Debug, Pread, Pwrite)]
+struct Sections {
+    magic: u16,
+    sections: [Section; 4],
+}
+
+#[test]
+fn test_error_context() {
+    let bytes = [0u8; 24];
+    let err = bytes.pread_with::<Sections>(0, LE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Sections.sections[3].size at 0x18: type is too big (2) for 0"
+    );
+    assert!(matches!(
+        err.root_cause(),
+        scroll::Error::TooBig { size: 2, len: 0 }
+    ));
+
+    let mut bytes = [0u8; 9];
+    let section = Section { offset: 0, size: 1 };
+    let err = bytes.pwrite_with(section, 4, LE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Section.size at 0x8: type is too big (2) for 1"
+    );
+    // offsets are in the buffer read from, not in the value
+    let bytes = [0u8; 26];
+    let err = bytes.pread_with::<Sections>(2, LE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Sections.sections[3].size at 0x1a: type is too big (2) for 0"
+    );
+}
+
+/// A goblin style context: the endianness and whether words are 64 bits wide
+#[derive(Debug, Clone, Copy, Default)]
+struct Container {
+    endian: scroll::Endian,
+    is_64: bool,
+}
+
+#[derive(Debug, PartialEq)]
+struct Word(u64);
+
+impl<'a> scroll::ctx::TryFromCtx<'a, Container> for Word {
+    type Error = scroll::Error;
+    fn try_from_ctx(src: &'a [u8], ctx: Container) -> Result<(Self, usize), Self::Error> {
+        let offset = &mut 0;
+        let word = if ctx.is_64 {
+            src.gread_with::<u64>(offset, ctx.endian)?
+        } else {
+            src.gread_with::<u32>(offset, ctx.endian)?.into()
+        };
+        Ok((Word(word), *offset))
+    }
+}
+
+impl scroll::ctx::TryIntoCtx<Container> for &Word {
+    type Error = scroll::Error;
+    fn try_into_ctx(self, dst: &mut [u8], ctx: Container) -> Result<usize, Self::Error> {
+        if ctx.is_64 {
+            dst.pwrite_with(self.0, 0, ctx.endian)
+        } else {
+            dst.pwrite_with(self.0 as u32, 0, ctx.endian)
+        }
+    }
+}
+
+impl scroll::ctx::SizeWith<Container> for Word {
+    fn size_with(ctx: &Container) -> usize {
+        if ctx.is_64 { 8 } else { 4 }
+    }
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite, SizeWith)]
+#[scroll(ctx = Container)]
+struct ContainerHeader {
+    #[scroll(ctx = ctx.endian)]
+    magic: u16,
+    entry: Word,
+    #[scroll(ctx = ctx.endian)]
+    flags: [u16; 2],
+}
+
+#[derive(Debug, PartialEq, IOread, IOwrite, SizeWith)]
+#[scroll(ctx = Container)]
+struct ContainerPair(
+    #[scroll(ctx = ctx.endian)] u16,
+    #[scroll(ctx = ctx.endian)] u32,
+);
+
+#[test]
+fn test_container_ctx() {
+    let bytes = [0x7f, 0x45, 1, 2, 3, 4, 5, 6, 7, 8, 0, 1, 0, 2];
+    let ctx = Container {
+        endian: BE,
+        is_64: true,
+    };
+    let header: ContainerHeader = bytes.pread_with(0, ctx).unwrap();
+    assert_eq!(
+        header,
+        ContainerHeader {
+            magic: 0x7f45,
+            entry: Word(0x0102030405060708),
+            flags: [1, 2],
+        }
+    );
+    assert_eq!(ContainerHeader::size_with(&ctx), bytes.len());
+    let mut out = [0u8; 14];
+    assert_eq!(out.pwrite_with(&header, 0, ctx).unwrap(), bytes.len());
+    assert_eq!(out, bytes);
+
+    let ctx = Container {
+        endian: LE,
+        is_64: false,
+    };
+    let header: ContainerHeader = bytes.pread_with(0, ctx).unwrap();
+    assert_eq!(header.entry, Word(0x04030201));
+    assert_eq!(header.flags, [0x0605, 0x0807]);
+    assert_eq!(ContainerHeader::size_with(&ctx), 10);
+
+    let mut cursor = std::io::Cursor::new(vec![]);
+    cursor.iowrite_with(ContainerPair(1, 2), ctx).unwrap();
+    assert_eq!(cursor.get_ref(), &[1, 0, 2, 0, 0, 0]);
+    cursor.set_position(0);
+    let pair: ContainerPair = cursor.ioread_with(ctx).unwrap();
+    assert_eq!(pair, ContainerPair(1, 2));
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite, SizeWith)]
+#[scroll(tag = u8)]
+enum Message {
+    #[scroll(tag_value = 1)]
+    Ping { seq: u32 },
+    #[scroll(tag_value = 2)]
+    Data(u16, [u8; 3]),
+    #[scroll(tag_value = 0xff)]
+    Quit,
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite)]
+#[scroll(tag = u16)]
+#[repr(u16)]
+enum Record<'a> {
+    Name(#[scroll(ctx = scroll::ctx::StrCtx::Length(2))] &'a str) = 0x100,
+    Blob {
+        len: u8,
+        #[scroll(ctx = len as usize)]
+        data: &'a [u8],
+    } = 0x200,
+}
+
+#[test]
+fn test_tagged_enum() {
+    let bytes = [1, 0xde, 0xad, 0xbe, 0xef, 2, 0, 1, 7, 8, 9, 0xff];
+    let offset = &mut 0;
+    let ping: Message = bytes.gread_with(offset, BE).unwrap();
+    assert_eq!(ping, Message::Ping { seq: 0xdeadbeef });
+    let data: Message = bytes.gread_with(offset, BE).unwrap();
+    assert_eq!(data, Message::Data(1, [7, 8, 9]));
+    let quit: Message = bytes.gread_with(offset, BE).unwrap();
+    assert_eq!(quit, Message::Quit);
+    assert_eq!(*offset, bytes.len());
+    assert_eq!(Message::size_with(&BE), 6);
+
+    let mut out = [0u8; 12];
+    let offset = &mut 0;
+    for message in [ping, data, quit] {
+        out.gwrite_with(message, offset, BE).unwrap();
+    }
+    assert_eq!(out, bytes);
+
+    let err = [3u8, 0, 0, 0, 0].pread_with::<Message>(0, BE).unwrap_err();
+    assert!(matches!(err, scroll::Error::BadInput { size: 1, .. }));
+    let err = [1u8, 0, 0].pread_with::<Message>(0, BE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Message::Ping.seq at 0x1: type is too big (4) for 2"
+    );
+
+    let bytes = b"\x01\x00hi\x02\x00\x03abc";
+    let offset = &mut 0;
+    let name: Record = bytes.gread_with(offset, BE).unwrap();
+    assert_eq!(name, Record::Name("hi"));
+    let blob: Record = bytes.gread_with(offset, BE).unwrap();
+    assert_eq!(
+        blob,
+        Record::Blob {
+            len: 3,
+            data: b"abc"
+        }
+    );
+    let mut out = [0u8; 10];
+    let offset = &mut 0;
+    out.gwrite_with(&name, offset, BE).unwrap();
+    out.gwrite_with(&blob, offset, BE).unwrap();
+    assert_eq!(&out[..], &bytes[..]);
+}
+
+/// Fields named like the loop variables of the generated writes
+#[derive(Debug, PartialEq, Pread, Pwrite)]
+#[scroll(tag = u8)]
+enum Shadowing {
+    #[scroll(tag_value = 1)]
+    Loops {
+        i: [u16; 2],
+        #[scroll(len_prefix = u8)]
+        element: Vec<u16>,
+    },
+}
+
+#[test]
+fn test_tagged_enum_loop_fields() {
+    let value = Shadowing::Loops {
+        i: [1, 2],
+        element: vec![3],
+    };
+    let mut out = [0u8; 8];
+    assert_eq!(out.pwrite_with(&value, 0, LE).unwrap(), 8);
+    assert_eq!(out, [1, 1, 0, 2, 0, 1, 3, 0]);
+    assert_eq!(out.pread_with::<Shadowing>(0, LE).unwrap(), value);
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite)]
+struct Table<'a> {
+    num_entries: u8,
+    #[scroll(count = self.num_entries)]
+    entries: Vec<u16>,
+    #[scroll(len_prefix = u8)]
+    name: &'a str,
+    #[scroll(len_prefix = u16)]
+    sections: Vec<Section>,
+    #[scroll(until_eof)]
+    trailer: &'a [u8],
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite)]
+struct Words(#[scroll(until_eof)] Vec<u16>);
+
+#[derive(Debug, PartialEq, Pread, Pwrite)]
+struct Blob<'a> {
+    len: u32,
+    #[scroll(count = len)]
+    data: &'a [u8],
+}
+
+#[test]
+fn test_variable_length_fields() {
+    let bytes = [
+        2, 1, 2, 3, 4, 2, b'h', b'i', 0, 1, 0, 0, 0, 0x10, 0, 0x20, b'x', b'y', b'z',
+    ];
+    let table: Table = bytes.pread_with(0, BE).unwrap();
+    assert_eq!(
+        table,
+        Table {
+            num_entries: 2,
+            entries: vec![0x102, 0x304],
+            name: "hi",
+            sections: vec![Section {
+                offset: 0x10,
+                size: 0x20
+            }],
+            trailer: b"xyz",
+        }
+    );
+    let mut out = [0u8; 19];
+    assert_eq!(out.pwrite_with(&table, 0, BE).unwrap(), bytes.len());
+    assert_eq!(out, bytes);
+
+    let err = [3u8, 0, 1, 0, 2].pread_with::<Table>(0, BE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Table.entries[2] at 0x5: type is too big (2) for 0"
+    );
+    let long_name = "a".repeat(256);
+    let table = Table {
+        name: &long_name,
+        ..table
+    };
+    let err = [0u8; 300].pwrite_with(&table, 0, BE).unwrap_err();
+    assert!(matches!(
+        err.root_cause(),
+        scroll::Error::BadInput { size: 256, .. }
+    ));
+
+    let words: Words = [0u8, 1, 0, 2, 0, 3].pread_with(0, BE).unwrap();
+    assert_eq!(words, Words(vec![1, 2, 3]));
+    let err = [0u8, 1, 0].pread_with::<Words>(0, BE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Words.0[1] at 0x2: type is too big (2) for 1"
+    );
+    assert_eq!([0u8; 0].pwrite_with(Words(vec![]), 0, BE).unwrap(), 0);
+
+    let blob: Blob = b"\0\0\0\x03abcd".pread_with(0, BE).unwrap();
+    assert_eq!(blob.data, b"abc");
+    let mut out = [0u8; 7];
+    out.pwrite_with(&blob, 0, BE).unwrap();
+    assert_eq!(&out, b"\0\0\0\x03abc");
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite, SizeWith)]
+struct Versioned {
+    version: u8,
+    flags: u8,
+    #[scroll(if = self.version >= 2)]
+    timestamp: Option<u32>,
+    #[scroll(if = flags & 1 != 0)]
+    checksum: Option<u16>,
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite)]
+#[scroll(ctx = Container)]
+struct OptionalWord {
+    #[scroll(ctx = ctx.endian)]
+    kind: u8,
+    #[scroll(if = ctx.is_64, ctx = ctx.endian)]
+    high: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite)]
+#[scroll(tag = u8)]
+enum OptionalVariant {
+    #[scroll(tag_value = 1)]
+    Flagged {
+        flags: u8,
+        #[scroll(if = flags != 0)]
+        value: Option<u8>,
+    },
+}
+
+#[test]
+fn test_optional_fields() {
+    let v1 = [1u8, 1, 0xbe, 0xef];
+    let versioned: Versioned = v1.pread_with(0, BE).unwrap();
+    assert_eq!(
+        versioned,
+        Versioned {
+            version: 1,
+            flags: 1,
+            timestamp: None,
+            checksum: Some(0xbeef),
+        }
+    );
+    let mut out = [0u8; 4];
+    assert_eq!(out.pwrite_with(versioned, 0, BE).unwrap(), 4);
+    assert_eq!(out, v1);
+
+    let v2 = [2u8, 0, 0, 0, 0, 5];
+    let versioned: Versioned = v2.pread_with(0, BE).unwrap();
+    assert_eq!(versioned.timestamp, Some(5));
+    assert_eq!(versioned.checksum, None);
+    let mut out = [0u8; 6];
+    assert_eq!(out.pwrite_with(versioned, 0, BE).unwrap(), 6);
+    assert_eq!(out, v2);
+    assert_eq!(Versioned::size_with(&BE), 8);
+
+    let err = [2u8, 0, 0].pread_with::<Versioned>(0, BE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Versioned.timestamp at 0x2: type is too big (4) for 1"
+    );
+    // a field which couldn't be read back isn't written
+    let mismatched = Versioned {
+        version: 1,
+        flags: 0,
+        timestamp: Some(5),
+        checksum: None,
+    };
+    let err = out.pwrite_with(mismatched, 0, BE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Versioned.timestamp at 0x2: bad input presence doesn't match: self.version >= 2 (0)"
+    );
+
+    let bytes = [7u8, 1, 0, 0, 0];
+    let ctx = Container {
+        endian: LE,
+        is_64: true,
+    };
+    let word: OptionalWord = bytes.pread_with(0, ctx).unwrap();
+    assert_eq!(
+        word,
+        OptionalWord {
+            kind: 7,
+            high: Some(1)
+        }
+    );
+    let ctx = Container {
+        is_64: false,
+        ..ctx
+    };
+    let word: OptionalWord = bytes.pread_with(0, ctx).unwrap();
+    assert_eq!(
+        word,
+        OptionalWord {
+            kind: 7,
+            high: None
+        }
+    );
+    let mut out = [0u8; 5];
+    let high = OptionalWord {
+        kind: 7,
+        high: Some(1),
+    };
+    assert!(out.pwrite_with(high, 0, ctx).is_err());
+
+    let flagged = OptionalVariant::Flagged {
+        flags: 1,
+        value: Some(9),
+    };
+    assert_eq!(out.pwrite_with(&flagged, 0, LE).unwrap(), 3);
+    assert_eq!(
+        out[..3].pread_with::<OptionalVariant>(0, LE).unwrap(),
+        flagged
+    );
+    let unflagged = OptionalVariant::Flagged {
+        flags: 0,
+        value: Some(9),
+    };
+    assert!(out.pwrite_with(unflagged, 0, LE).is_err());
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite, SizeWith)]
+#[scroll(magic = b"\x7fELF")]
+struct ElfIdent {
+    class: u8,
+    #[scroll(pad = 3)]
+    version: u8,
+    #[scroll(align = 4)]
+    entry: u32,
+    #[scroll(magic = b"END")]
+    trailer: u16,
+}
+
+#[test]
+fn test_pad_align_magic() {
+    let bytes = [
+        0x7f, b'E', b'L', b'F', 1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0x10, b'E', b'N', b'D', 0, 5,
+    ];
+    let ident: ElfIdent = bytes.pread_with(0, BE).unwrap();
+    assert_eq!(
+        ident,
+        ElfIdent {
+            class: 1,
+            version: 2,
+            entry: 0x10,
+            trailer: 5,
+        }
+    );
+    assert_eq!(ElfIdent::size_with(&BE), bytes.len());
+    // reserved bytes are zeroed
+    let mut out = [0xffu8; 21];
+    assert_eq!(out.pwrite_with(&ident, 0, BE).unwrap(), bytes.len());
+    assert_eq!(out, bytes);
+
+    let mut bad = bytes;
+    bad[1] = b'e';
+    let err = bad.pread_with::<ElfIdent>(0, BE).unwrap_err();
+    assert!(err.to_string().starts_with("ElfIdent.magic at 0x0: "));
+    assert!(matches!(
+        err.root_cause(),
+        scroll::Error::BadInput {
+            size: 4,
+            msg: "bad magic"
+        }
+    ));
+    let mut bad = bytes;
+    bad[18] = b'd';
+    let err = bad.pread_with::<ElfIdent>(0, BE).unwrap_err();
+    assert!(err.to_string().starts_with("ElfIdent.trailer at 0x10: "));
+    // the padding must be there, too
+    let err = bytes[..6].pread_with::<ElfIdent>(0, BE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ElfIdent.version at 0x5: type is too big (3) for 1"
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Pread, Pwrite, IOread, IOwrite, SizeWith)]
+struct Padded {
+    a: u8,
+    #[scroll(pad = 3)]
+    b: u32,
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite, IOread, IOwrite, SizeWith)]
+struct Aligned {
+    a: u8,
+    #[scroll(align = 4)]
+    b: u32,
+    c: u16,
+    #[scroll(align = 8)]
+    d: [u8; 2],
+}
+
+#[test]
+fn test_pad_align_io() {
+    let padded = Padded { a: 1, b: 2 };
+    let bytes = [1, 0, 0, 0, 2, 0, 0, 0];
+    assert_eq!(Padded::size_with(&LE), bytes.len());
+    let mut cursor = std::io::Cursor::new(vec![]);
+    cursor.iowrite_with(padded, LE).unwrap();
+    assert_eq!(cursor.get_ref(), &bytes);
+    cursor.set_position(0);
+    assert_eq!(cursor.ioread_with::<Padded>(LE).unwrap(), padded);
+    // reserved bytes are skipped on read and zeroed on write
+    let mut out = [0xffu8; 8];
+    out[..4].copy_from_slice(&[1, 9, 9, 9]);
+    assert_eq!(
+        out.cread_with::<Padded>(0, LE),
+        Padded { a: 1, b: u32::MAX }
+    );
+    out.cwrite_with(padded, 0, LE);
+    assert_eq!(out, bytes);
+
+    let aligned = Aligned {
+        a: 1,
+        b: 2,
+        c: 3,
+        d: [4, 5],
+    };
+    let bytes = [1, 0, 0, 0, 0, 0, 0, 2, 0, 3, 0, 0, 0, 0, 0, 0, 4, 5];
+    assert_eq!(Aligned::size_with(&BE), bytes.len());
+    let mut out = [0xffu8; 18];
+    out.cwrite_with(&aligned, 0, BE);
+    assert_eq!(out, bytes);
+    assert_eq!(out.cread_with::<Aligned>(0, BE), aligned);
+    // the same layout as Pread and Pwrite
+    assert_eq!(bytes.pread_with::<Aligned>(0, BE).unwrap(), aligned);
+    let mut cursor = std::io::Cursor::new(&bytes[..]);
+    assert_eq!(cursor.ioread_with::<Aligned>(BE).unwrap(), aligned);
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite, IOread, IOwrite, SizeWith)]
+#[scroll(endian = "big")]
+struct NetHeader {
+    len: u16,
+    #[scroll(endian = "little")]
+    crc: u32,
+    #[scroll(endian = "ctx")]
+    id: u16,
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite)]
+#[scroll(endian = "big")]
+#[repr(u16)]
+enum Opcode {
+    Read = 1,
+    Write = 2,
+}
+
+#[test]
+fn test_endian_attribute() {
+    let bytes = [0, 1, 1, 0, 0, 0, 2, 0];
+    let expected = NetHeader {
+        len: 1,
+        crc: 1,
+        id: 2,
+    };
+    assert_eq!(bytes.pread_with::<NetHeader>(0, LE).unwrap(), expected);
+    assert_eq!(bytes.pread_with::<NetHeader>(0, BE).unwrap().id, 0x200);
+    let mut out = [0u8; 8];
+    out.pwrite_with(&expected, 0, LE).unwrap();
+    assert_eq!(out, bytes);
+    assert_eq!(NetHeader::size_with(&LE), 8);
+
+    let mut cursor = std::io::Cursor::new(&bytes[..]);
+    let header: NetHeader = cursor.ioread_with(LE).unwrap();
+    assert_eq!(header, expected);
+    let mut out = std::io::Cursor::new(Vec::new());
+    out.iowrite_with(header, LE).unwrap();
+    assert_eq!(out.into_inner(), bytes);
+
+    assert_eq!([0u8, 2].pread_with::<Opcode>(0, LE).unwrap(), Opcode::Write);
+    let mut out = [0u8; 2];
+    out.pwrite_with(Opcode::Read, 0, LE).unwrap();
+    assert_eq!(out, [0, 1]);
+}
+
+fn check_range(range: &Range) -> Result<(), scroll::Error> {
+    if range.start <= range.end {
+        Ok(())
+    } else {
+        Err(scroll::Error::BadInput {
+            size: 4,
+            msg: "start is after end",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Pread)]
+#[scroll(validate = check_range)]
+struct Range {
+    #[scroll(assert = start != 0)]
+    start: u16,
+    #[scroll(assert = self.end < 0x1000)]
+    end: u16,
+}
+
+#[test]
+fn test_validate_and_assert() {
+    let range: Range = [0u8, 1, 0, 2].pread_with(0, BE).unwrap();
+    assert_eq!(range, Range { start: 1, end: 2 });
+
+    let err = [0u8, 0, 0, 2].pread_with::<Range>(0, BE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Range.start at 0x0: bad input assertion failed: start != 0 (2)"
+    );
+    let err = [0xffu8, 0, 1, 0, 0x10, 0]
+        .pread_with::<Range>(2, BE)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Range.end at 0x4: bad input assertion failed: self.end < 0x1000 (2)"
+    );
+    let err = [0u8, 2, 0, 1].pread_with::<Range>(0, BE).unwrap_err();
+    assert!(matches!(
+        err,
+        scroll::Error::BadInput {
+            msg: "start is after end",
+            ..
+        }
+    ));
+}
+
+#[derive(Debug)]
+enum FormatError {
+    Scroll(scroll::Error),
+    BadVersion(u8),
+}
+
+impl From<scroll::Error> for FormatError {
+    fn from(err: scroll::Error) -> Self {
+        FormatError::Scroll(err)
+    }
+}
+
+fn check_version(header: &VersionHeader) -> Result<(), FormatError> {
+    match header.version {
+        0..=3 => Ok(()),
+        version => Err(FormatError::BadVersion(version)),
+    }
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite)]
+#[scroll(error = FormatError, validate = check_version)]
+struct VersionHeader {
+    version: u8,
+    #[scroll(len_prefix = u8)]
+    items: Vec<u16>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Pread, Pwrite)]
+#[scroll(error = FormatError)]
+#[repr(u8)]
+enum Kind {
+    Plain = 1,
+    Packed = 2,
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite)]
+#[scroll(error = FormatError, magic = b"VF")]
+struct VersionFile {
+    header: VersionHeader,
+    kind: Kind,
+}
+
+#[test]
+fn test_custom_error() {
+    let bytes = [b'V', b'F', 1, 2, 0, 1, 0, 2, 2];
+    let file: VersionFile = bytes.pread_with(0, BE).unwrap();
+    assert_eq!(
+        file,
+        VersionFile {
+            header: VersionHeader {
+                version: 1,
+                items: vec![1, 2],
+            },
+            kind: Kind::Packed,
+        }
+    );
+    let mut out = [0u8; 9];
+    assert_eq!(out.pwrite_with(&file, 0, BE).unwrap(), bytes.len());
+    assert_eq!(out, bytes);
+
+    let mut bad = bytes;
+    bad[2] = 7;
+    assert!(matches!(
+        bad.pread_with::<VersionFile>(0, BE),
+        Err(FormatError::BadVersion(7))
+    ));
+    assert!(matches!(
+        bytes[..7].pread_with::<VersionFile>(0, BE),
+        Err(FormatError::Scroll(scroll::Error::TooBig {
+            size: 2,
+            len: 1
+        }))
+    ));
+    assert!(matches!(
+        bytes[1..].pread_with::<VersionFile>(0, BE),
+        Err(FormatError::Scroll(scroll::Error::BadInput { .. }))
+    ));
+    assert!(matches!(
+        [3u8].pread_with::<Kind>(0, BE),
+        Err(FormatError::Scroll(scroll::Error::BadInput { .. }))
+    ));
+    assert!(matches!(
+        [0u8; 8].pwrite_with(&file, 0, BE),
+        Err(FormatError::Scroll(scroll::Error::TooBig { .. }))
+    ));
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct DosDate {
+    year: u16,
+    month: u8,
+    day: u8,
+}
+
+fn read_dos_date(
+    src: &[u8],
+    offset: &mut usize,
+    ctx: scroll::Endian,
+) -> Result<DosDate, scroll::Error> {
+    let raw: u16 = src.gread_with(offset, ctx)?;
+    Ok(DosDate {
+        year: 1980 + (raw >> 9),
+        month: ((raw >> 5) & 0xf) as u8,
+        day: (raw & 0x1f) as u8,
+    })
+}
+
+fn write_dos_date(
+    date: &DosDate,
+    dst: &mut [u8],
+    offset: &mut usize,
+    ctx: scroll::Endian,
+) -> Result<usize, scroll::Error> {
+    let raw = ((date.year - 1980) << 9) | ((date.month as u16) << 5) | date.day as u16;
+    dst.gwrite_with(raw, offset, ctx)
+}
+
+fn dos_date_size(_: &scroll::Endian) -> usize {
+    2
+}
+
+fn read_bcd(src: &[u8], offset: &mut usize, _: scroll::Endian) -> Result<u8, scroll::Error> {
+    let start = *offset;
+    let bcd: u8 = src.gread(offset)?;
+    if bcd >> 4 > 9 || bcd & 0xf > 9 {
+        *offset = start;
+        return Err(scroll::Error::BadInput {
+            size: 1,
+            msg: "invalid bcd",
+        });
+    }
+    Ok((bcd >> 4) * 10 + (bcd & 0xf))
+}
+
+fn write_bcd(
+    n: &u8,
+    dst: &mut [u8],
+    offset: &mut usize,
+    _: scroll::Endian,
+) -> Result<usize, scroll::Error> {
+    dst.gwrite(((n / 10) << 4) | (n % 10), offset)
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite, SizeWith)]
+struct Archived {
+    #[scroll(read_with = read_dos_date, write_with = write_dos_date, size_with = dos_date_size)]
+    modified: DosDate,
+    #[scroll(read_with = read_bcd, write_with = write_bcd)]
+    revision: u8,
+    size: u32,
+}
+
+#[test]
+fn test_read_write_with() {
+    let bytes = [0x59, 0x4e, 0x42, 0x00, 0x00, 0x01, 0x00];
+    let archived: Archived = bytes.pread_with(0, BE).unwrap();
+    assert_eq!(
+        archived,
+        Archived {
+            modified: DosDate {
+                year: 2024,
+                month: 10,
+                day: 14,
+            },
+            revision: 42,
+            size: 256,
+        }
+    );
+    assert_eq!(Archived::size_with(&BE), 7);
+    let mut out = [0u8; 7];
+    assert_eq!(out.pwrite_with(&archived, 0, BE).unwrap(), 7);
+    assert_eq!(out, bytes);
+
+    let mut bad = bytes;
+    bad[2] = 0x4a;
+    let err = bad.pread_with::<Archived>(0, BE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Archived.revision at 0x2: bad input invalid bcd (1)"
+    );
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Pread, Pwrite, SizeWith, IOread, IOwrite)]
+#[scroll(bitfield = u16, bit_order = "msb", endian = "big")]
+struct DnsFlags {
+    #[scroll(bits = 1)]
+    response: bool,
+    #[scroll(bits = 4)]
+    opcode: u8,
+    #[scroll(bits = 1)]
+    authoritative: bool,
+    #[scroll(bits = 1)]
+    truncated: bool,
+    #[scroll(bits = 1)]
+    recursion_desired: bool,
+    #[scroll(bits = 1)]
+    recursion_available: bool,
+    #[scroll(bits = 3)]
+    zero: u8,
+    #[scroll(bits = 4)]
+    rcode: u8,
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite, SizeWith)]
+struct DnsHeader {
+    id: u16,
+    flags: DnsFlags,
+    questions: u16,
+}
+
+#[derive(Debug, PartialEq, Pread, Pwrite, IOwrite, SizeWith)]
+#[scroll(bitfield = u8)]
+struct Nibbles(#[scroll(bits = 4)] u8, #[scroll(bits = 4)] u8);
+
+#[test]
+fn test_bitfield() {
+    let bytes = [0x12, 0x34, 0x81, 0x83, 0x00, 0x01];
+    let header: DnsHeader = bytes.pread_with(0, LE).unwrap();
+    let flags = DnsFlags {
+        response: true,
+        opcode: 0,
+        authoritative: false,
+        truncated: false,
+        recursion_desired: true,
+        recursion_available: true,
+        zero: 0,
+        rcode: 3,
+    };
+    assert_eq!(
+        header,
+        DnsHeader {
+            id: 0x3412,
+            flags,
+            questions: 0x100,
+        }
+    );
+    assert_eq!(DnsHeader::size_with(&LE), 6);
+    let mut out = [0u8; 6];
+    assert_eq!(out.pwrite_with(&header, 0, LE).unwrap(), 6);
+    assert_eq!(out, bytes);
+
+    let mut cursor = std::io::Cursor::new(&bytes[2..4]);
+    assert_eq!(cursor.ioread_with::<DnsFlags>(LE).unwrap(), flags);
+    let mut written = std::io::Cursor::new(Vec::new());
+    written.iowrite_with(flags, LE).unwrap();
+    assert_eq!(written.into_inner(), [0x81, 0x83]);
+
+    let nibbles: Nibbles = [0x2a].pread(0).unwrap();
+    assert_eq!(nibbles, Nibbles(0xa, 0x2));
+    assert_eq!([0u8; 1].pwrite(Nibbles(0x1, 0xf), 0).unwrap(), 1);
+    let err = [0u8; 1].pwrite(Nibbles(0x10, 0), 0).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Nibbles.0 at 0x0: bad input value doesn't fit in its bits (4)"
+    );
+    // IOwrite can't fail, so it truncates instead
+    let mut written = std::io::Cursor::new(Vec::new());
+    written.iowrite_with(Nibbles(0x1a, 0x2), LE).unwrap();
+    assert_eq!(written.into_inner(), [0x2a]);
+}
+
+#[derive(Debug, PartialEq, Clone, Pread, Pwrite, IOread, IOwrite, SizeWith)]
+struct Sector {
+    id: u32,
+    data: [u8; 300],
+    checksum: u16,
+}
+
+#[test]
+fn test_large_ioread_iowrite() {
+    let sector = Sector {
+        id: 7,
+        data: core::array::from_fn(|i| i as u8),
+        checksum: 0xbeef,
+    };
+    assert_eq!(Sector::size_with(&LE), 306);
+    let mut cursor = std::io::Cursor::new(Vec::new());
+    cursor.iowrite_with(sector.clone(), BE).unwrap();
+    let bytes = cursor.into_inner();
+    assert_eq!(bytes.len(), 306);
+    assert_eq!(bytes[..6], [0, 0, 0, 7, 0, 1]);
+    assert_eq!(bytes.pread_with::<Sector>(0, BE).unwrap(), sector);
+
+    let mut cursor = std::io::Cursor::new(&bytes);
+    assert_eq!(cursor.ioread_with::<Sector>(BE).unwrap(), sector);
+    // a truncated stream is an error rather than a panic
+    let mut cursor = std::io::Cursor::new(&bytes[..300]);
+    assert!(cursor.ioread_with::<Sector>(BE).is_err());
+}
+
+#[derive(Debug, Clone, PartialEq, Pread, Pwrite)]
+struct Triple {
+    a: u64,
+    b: u64,
+    c: u64,
+}
+
+#[test]
+fn test_vec_writer_derived() {
+    // larger than the room VecWriter starts with, so the derived impl fails with a TooBig inside
+    // the context of its field before the writer grows
+    let triple = Triple { a: 1, b: 2, c: 3 };
+    let mut writer = scroll::VecWriter::new();
+    assert_eq!(writer.pwrite_with(&triple, 0, LE).unwrap(), 24);
+    assert_eq!(
+        writer.as_slice().pread_with::<Triple>(0, LE).unwrap(),
+        triple
+    );
+
+    let sector = Sector {
+        id: 7,
+        data: core::array::from_fn(|i| i as u8),
+        checksum: 0xbeef,
+    };
+    let mut bytes = Vec::new();
+    assert_eq!(bytes.try_iowrite_with(&sector, BE).unwrap(), 306);
+    assert_eq!(bytes.pread_with::<Sector>(0, BE).unwrap(), sector);
+}
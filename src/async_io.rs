@@ -0,0 +1,169 @@
+//! The async IO traits of the `tokio` and `futures-io` features, whose stream traits have the same
+//! methods, so that both modules share one implementation.
+
+/// Defines `AsyncIOread`, `AsyncBufIOread` and `AsyncIOwrite`, with their blanket impls, over the
+/// `AsyncRead`, `AsyncBufRead` and `AsyncWrite` traits in scope, which the docs call `$streams`.
+/// The invoking module provides `read_exact` and `write_all` functions for them.
+macro_rules! async_io_traits {
+    ($streams:literal) => {
+        #[doc = concat!("An extension trait to ", $streams, " `AsyncRead` streams, reading types which implement")]
+        /// [`FromCtx`](../ctx/trait.FromCtx.html) and [`SizeWith`](../ctx/trait.SizeWith.html), like
+        /// [IOread](../trait.IOread.html) does for `std::io::Read`.
+        pub trait AsyncIOread<Ctx: Copy>: AsyncRead + Unpin {
+            /// Reads the type `N` from `Self`, with a default parsing context.
+            #[inline]
+            fn ioread<N: $crate::ctx::FromCtx<Ctx> + $crate::ctx::SizeWith<Ctx>>(
+                &mut self,
+            ) -> impl ::core::future::Future<Output = ::std::io::Result<N>>
+            where
+                Ctx: Default,
+            {
+                self.ioread_with(Ctx::default())
+            }
+
+            /// Reads the type `N` from `Self`, with the parsing context `ctx`.
+            ///
+            /// The returned future isn't cancel-safe: dropping it before it completes loses the
+            /// bytes it already read.
+            fn ioread_with<N: $crate::ctx::FromCtx<Ctx> + $crate::ctx::SizeWith<Ctx>>(
+                &mut self,
+                ctx: Ctx,
+            ) -> impl ::core::future::Future<Output = ::std::io::Result<N>> {
+                async move {
+                    let size = N::size_with(&ctx);
+                    let mut scratch = [0u8; $crate::lesser::SCRATCH_SIZE];
+                    let mut heap;
+                    let buf = match scratch.get_mut(..size) {
+                        Some(buf) => buf,
+                        None => {
+                            heap = $crate::lesser::heap_scratch(size)?;
+                            &mut heap[..]
+                        }
+                    };
+                    read_exact(self, buf).await?;
+                    Ok(N::from_ctx(buf, ctx))
+                }
+            }
+        }
+
+        /// Types that implement `AsyncRead` get methods defined in `AsyncIOread`
+        /// for free.
+        impl<Ctx: Copy, R: AsyncRead + Unpin + ?Sized> AsyncIOread<Ctx> for R {}
+
+        #[doc = concat!("An extension trait to ", $streams, " `AsyncBufRead` streams, reading owned types which")]
+        /// only implement [`TryFromCtx`](../ctx/trait.TryFromCtx.html), like
+        /// [BufIOread](../trait.BufIOread.html) does for `std::io::BufRead`.
+        pub trait AsyncBufIOread<Ctx: Copy>: AsyncBufRead + Unpin {
+            /// Reads the type `N` from `Self`, with a default parsing context.
+            #[inline]
+            fn try_ioread<N: for<'a> $crate::ctx::TryFromCtx<'a, Ctx, Error = $crate::Error>>(
+                &mut self,
+            ) -> impl ::core::future::Future<Output = $crate::Result<N>>
+            where
+                Ctx: Default,
+            {
+                self.try_ioread_with(Ctx::default())
+            }
+
+            /// Reads the type `N` from `Self`, with the parsing context `ctx`.
+            ///
+            /// The returned future isn't cancel-safe: the bytes of a value spanning several
+            /// buffers of the stream are consumed as they come in, so dropping the future before
+            /// it completes loses them.
+            fn try_ioread_with<N: for<'a> $crate::ctx::TryFromCtx<'a, Ctx, Error = $crate::Error>>(
+                &mut self,
+                ctx: Ctx,
+            ) -> impl ::core::future::Future<Output = $crate::Result<N>> {
+                let mut parser = $crate::lesser::Incremental::default();
+                ::core::future::poll_fn(move |cx| {
+                    let mut reader = ::core::pin::Pin::new(&mut *self);
+                    loop {
+                        let available = ::core::task::ready!(reader.as_mut().poll_fill_buf(cx))?;
+                        let (n, consumed) = parser.feed(available, ctx)?;
+                        reader.as_mut().consume(consumed);
+                        if let Some(n) = n {
+                            return ::core::task::Poll::Ready(Ok(n));
+                        }
+                    }
+                })
+            }
+        }
+
+        /// Types that implement `AsyncBufRead` get methods defined in `AsyncBufIOread`
+        /// for free.
+        impl<Ctx: Copy, R: AsyncBufRead + Unpin + ?Sized> AsyncBufIOread<Ctx> for R {}
+
+        #[doc = concat!("An extension trait to ", $streams, " `AsyncWrite` streams, writing types which implement")]
+        /// [`IntoCtx`](../ctx/trait.IntoCtx.html) and [`SizeWith`](../ctx/trait.SizeWith.html), or
+        /// only [`TryIntoCtx`](../ctx/trait.TryIntoCtx.html), like [IOwrite](../trait.IOwrite.html)
+        /// does for `std::io::Write`.
+        pub trait AsyncIOwrite<Ctx: Copy>: AsyncWrite + Unpin {
+            /// Writes the type `N` into `Self`, with a default parsing context.
+            #[inline]
+            fn iowrite<N: $crate::ctx::SizeWith<Ctx> + $crate::ctx::IntoCtx<Ctx>>(
+                &mut self,
+                n: N,
+            ) -> impl ::core::future::Future<Output = ::std::io::Result<()>>
+            where
+                Ctx: Default,
+            {
+                self.iowrite_with(n, Ctx::default())
+            }
+
+            /// Writes the type `N` into `Self`, with the parsing context `ctx`.
+            fn iowrite_with<N: $crate::ctx::SizeWith<Ctx> + $crate::ctx::IntoCtx<Ctx>>(
+                &mut self,
+                n: N,
+                ctx: Ctx,
+            ) -> impl ::core::future::Future<Output = ::std::io::Result<()>> {
+                async move {
+                    let size = N::size_with(&ctx);
+                    let mut scratch = [0u8; $crate::lesser::SCRATCH_SIZE];
+                    let mut heap;
+                    let buf = match scratch.get_mut(..size) {
+                        Some(buf) => buf,
+                        None => {
+                            heap = $crate::lesser::heap_scratch(size)?;
+                            &mut heap[..]
+                        }
+                    };
+                    n.into_ctx(buf, ctx);
+                    write_all(self, buf).await
+                }
+            }
+
+            /// Writes the type `N` into `Self`, with a default parsing context, and returns the
+            /// number of bytes written.
+            #[inline]
+            fn try_iowrite<N: $crate::ctx::TryIntoCtx<Ctx, Error = $crate::Error> + Clone>(
+                &mut self,
+                n: N,
+            ) -> impl ::core::future::Future<Output = $crate::Result<usize>>
+            where
+                Ctx: Default,
+            {
+                self.try_iowrite_with(n, Ctx::default())
+            }
+
+            /// Writes the type `N` into `Self`, with the parsing context `ctx`, and returns the
+            /// number of bytes written; see
+            /// [IOwrite::try_iowrite_with](../trait.IOwrite.html#method.try_iowrite_with).
+            fn try_iowrite_with<N: $crate::ctx::TryIntoCtx<Ctx, Error = $crate::Error> + Clone>(
+                &mut self,
+                n: N,
+                ctx: Ctx,
+            ) -> impl ::core::future::Future<Output = $crate::Result<usize>> {
+                async move {
+                    let mut writer = $crate::VecWriter::new();
+                    let size = writer.pwrite_with(n, 0, ctx)?;
+                    write_all(self, writer.as_slice()).await?;
+                    Ok(size)
+                }
+            }
+        }
+
+        /// Types that implement `AsyncWrite` get methods defined in `AsyncIOwrite`
+        /// for free.
+        impl<Ctx: Copy, W: AsyncWrite + Unpin + ?Sized> AsyncIOwrite<Ctx> for W {}
+    };
+}
@@ -0,0 +1,563 @@
+use crate::ctx::{FromCtx, IntoCtx, SizeWith};
+use crate::endian::Endian;
+use crate::error::{self, Error};
+
+/// The order in which the bits of a byte are read or written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BitOrder {
+    /// The most significant bit of a byte comes first, and values are read from their most
+    /// significant bit down, as in H.264 or MPEG headers
+    #[default]
+    Msb,
+    /// The least significant bit of a byte comes first, and values are read from their least
+    /// significant bit up, as in DEFLATE
+    Lsb,
+}
+
+/// The largest value, in bytes, [BitReader::read_with](struct.BitReader.html#method.read_with) and
+/// [BitWriter::write_with](struct.BitWriter.html#method.write_with) can handle
+const MAX_BYTES: usize = 16;
+
+#[inline]
+fn mask(bits: u32) -> u64 {
+    if bits == 0 {
+        0
+    } else {
+        u64::MAX >> (64 - bits)
+    }
+}
+
+#[inline]
+fn check_width(bits: u32) -> error::Result<()> {
+    if bits > 64 {
+        return Err(Error::BadInput {
+            size: bits as usize,
+            msg: "at most 64 bits can be read or written at once",
+        });
+    }
+    Ok(())
+}
+
+/// A byte slice read bit by bit, for formats whose fields don't start on byte boundaries.
+///
+/// Unsigned and signed integers of any width up to 64 bits are read in the reader's
+/// [BitOrder](enum.BitOrder.html), as are the Exp-Golomb codes of H.264. Whole values, e.g. a
+/// `u32`, are read at any bit position with [read_with](#method.read_with) and an `Endian`.
+///
+/// Positions and lengths are in bits, and so are the `size` and `len` of the `Error::TooBig`
+/// returned when a read runs past the end of the slice. A failed read leaves the position
+/// untouched.
+///
+/// # Example
+/// ```rust
+/// use scroll::{BitOrder, BitReader, BE};
+/// // an H.264 NAL unit header followed by the start of a slice header
+/// let bytes = [0x65, 0xb0, 0x12, 0x30];
+/// let mut bits = BitReader::new(&bytes[..], BitOrder::Msb);
+/// assert!(!bits.read_bool().unwrap());
+/// assert_eq!(bits.read_bits(2).unwrap(), 3);
+/// assert_eq!(bits.read_bits(5).unwrap(), 5);
+/// assert_eq!(bits.read_exp_golomb().unwrap(), 0);
+/// assert_eq!(bits.read_exp_golomb().unwrap(), 2);
+/// assert_eq!(bits.position(), 12);
+/// assert_eq!(bits.read_with::<u16>(BE).unwrap(), 0x0123);
+/// assert_eq!(bits.remaining(), 4);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    order: BitOrder,
+}
+
+impl<'a> BitReader<'a> {
+    /// Creates a reader at the first bit of `bytes`
+    #[inline]
+    pub fn new(bytes: &'a [u8], order: BitOrder) -> Self {
+        BitReader {
+            bytes,
+            position: 0,
+            order,
+        }
+    }
+
+    /// The current position, in bits
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves the reader to the bit at `position`; it is checked by the next read
+    #[inline]
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// The bit order of this reader
+    #[inline]
+    pub fn order(&self) -> BitOrder {
+        self.order
+    }
+
+    /// The whole underlying slice
+    #[inline]
+    pub fn get_ref(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// The number of bits left after the current position
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        (self.bytes.len() * 8).saturating_sub(self.position)
+    }
+
+    /// Whether the current position is at the start of a byte
+    #[inline]
+    pub fn is_byte_aligned(&self) -> bool {
+        self.position % 8 == 0
+    }
+
+    #[inline]
+    fn ensure(&self, bits: usize) -> error::Result<()> {
+        let len = self.remaining();
+        if bits > len {
+            return Err(Error::TooBig { size: bits, len });
+        }
+        Ok(())
+    }
+
+    /// Reads `bits` bits without checking there are enough of them
+    fn take(&mut self, bits: u32) -> u64 {
+        let mut value = 0;
+        let mut done = 0;
+        while done < bits {
+            let byte = u64::from(self.bytes[self.position / 8]);
+            let offset = (self.position % 8) as u32;
+            let take = (8 - offset).min(bits - done);
+            value = match self.order {
+                BitOrder::Msb => (value << take) | ((byte >> (8 - offset - take)) & mask(take)),
+                BitOrder::Lsb => value | (((byte >> offset) & mask(take)) << done),
+            };
+            done += take;
+            self.position += take as usize;
+        }
+        value
+    }
+
+    /// Reads an unsigned integer `bits` wide, at most 64, and advances past it
+    pub fn read_bits(&mut self, bits: u32) -> error::Result<u64> {
+        check_width(bits)?;
+        self.ensure(bits as usize)?;
+        Ok(self.take(bits))
+    }
+
+    /// Reads a two's complement integer `bits` wide, at most 64, and advances past it
+    pub fn read_signed_bits(&mut self, bits: u32) -> error::Result<i64> {
+        let value = self.read_bits(bits)?;
+        if bits == 0 {
+            return Ok(0);
+        }
+        let shift = 64 - bits;
+        Ok(((value << shift) as i64) >> shift)
+    }
+
+    /// Reads an unsigned integer `bits` wide, at most 64, without advancing
+    pub fn peek_bits(&self, bits: u32) -> error::Result<u64> {
+        let mut reader = *self;
+        reader.read_bits(bits)
+    }
+
+    /// Reads a single bit
+    #[inline]
+    pub fn read_bool(&mut self) -> error::Result<bool> {
+        Ok(self.read_bits(1)? == 1)
+    }
+
+    /// Reads an unsigned Exp-Golomb code, the `ue(v)` of H.264; the bits following the leading
+    /// zeros and the one are read in the reader's bit order
+    pub fn read_exp_golomb(&mut self) -> error::Result<u64> {
+        let start = self.position;
+        let result = self.take_exp_golomb();
+        if result.is_err() {
+            self.position = start;
+        }
+        result
+    }
+
+    fn take_exp_golomb(&mut self) -> error::Result<u64> {
+        let mut zeros = 0;
+        while !self.read_bool()? {
+            zeros += 1;
+            if zeros > 63 {
+                return Err(Error::BadInput {
+                    size: zeros as usize,
+                    msg: "exp-golomb code is too long",
+                });
+            }
+        }
+        let suffix = self.read_bits(zeros)?;
+        Ok(mask(zeros) + suffix)
+    }
+
+    /// Reads a signed Exp-Golomb code, the `se(v)` of H.264
+    pub fn read_signed_exp_golomb(&mut self) -> error::Result<i64> {
+        let code = self.read_exp_golomb()?;
+        let magnitude = code.div_ceil(2) as i64;
+        Ok(if code % 2 == 1 { magnitude } else { -magnitude })
+    }
+
+    /// Reads an `N` whose bytes follow each other from the current bit position on, e.g. a `u32`
+    /// in the given byte order; `N` is at most 16 bytes
+    pub fn read_with<N: FromCtx<Endian> + SizeWith<Endian>>(
+        &mut self,
+        ctx: Endian,
+    ) -> error::Result<N> {
+        let size = N::size_with(&ctx);
+        if size > MAX_BYTES {
+            return Err(Error::BadInput {
+                size,
+                msg: "values read from bits can be at most 16 bytes",
+            });
+        }
+        self.ensure(size * 8)?;
+        let mut buf = [0u8; MAX_BYTES];
+        for byte in &mut buf[..size] {
+            *byte = self.take(8) as u8;
+        }
+        Ok(N::from_ctx(&buf[..size], ctx))
+    }
+
+    /// Advances the reader by `bits` bits
+    pub fn skip(&mut self, bits: usize) -> error::Result<()> {
+        self.ensure(bits)?;
+        self.position += bits;
+        Ok(())
+    }
+
+    /// Advances the reader to the start of the next byte, if it isn't already at one
+    pub fn align_to_byte(&mut self) -> error::Result<()> {
+        let aligned = self.position.next_multiple_of(8);
+        if aligned > self.bytes.len() * 8 {
+            return Err(Error::BadOffset(aligned));
+        }
+        self.position = aligned;
+        Ok(())
+    }
+}
+
+/// A mutable byte slice written bit by bit, the counterpart of [BitReader](struct.BitReader.html).
+///
+/// Only the written bits of a byte are changed, the others keep their value. Positions and lengths
+/// are in bits, including those of `Error::TooBig`, and a failed write leaves the position
+/// untouched.
+///
+/// # Example
+/// ```rust
+/// use scroll::{BitOrder, BitWriter, LE};
+/// let mut bytes = [0u8; 3];
+/// let mut bits = BitWriter::new(&mut bytes[..], BitOrder::Lsb);
+/// // the header of a final DEFLATE block with fixed Huffman codes
+/// bits.write_bool(true).unwrap();
+/// bits.write_bits(1, 2).unwrap();
+/// bits.write_with(0xabcdu16, LE).unwrap();
+/// assert!(bits.write_bits(0, 6).is_err());
+/// assert_eq!(bits.position(), 19);
+/// assert_eq!(bytes, [0x6b, 0x5e, 0x05]);
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct BitWriter<'a> {
+    bytes: &'a mut [u8],
+    position: usize,
+    order: BitOrder,
+}
+
+impl<'a> BitWriter<'a> {
+    /// Creates a writer at the first bit of `bytes`
+    #[inline]
+    pub fn new(bytes: &'a mut [u8], order: BitOrder) -> Self {
+        BitWriter {
+            bytes,
+            position: 0,
+            order,
+        }
+    }
+
+    /// The current position, in bits
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves the writer to the bit at `position`; it is checked by the next write
+    #[inline]
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// The bit order of this writer
+    #[inline]
+    pub fn order(&self) -> BitOrder {
+        self.order
+    }
+
+    /// The number of bits left after the current position
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        (self.bytes.len() * 8).saturating_sub(self.position)
+    }
+
+    /// Whether the current position is at the start of a byte
+    #[inline]
+    pub fn is_byte_aligned(&self) -> bool {
+        self.position % 8 == 0
+    }
+
+    /// Returns the underlying slice
+    #[inline]
+    pub fn into_inner(self) -> &'a mut [u8] {
+        self.bytes
+    }
+
+    #[inline]
+    fn ensure(&self, bits: usize) -> error::Result<()> {
+        let len = self.remaining();
+        if bits > len {
+            return Err(Error::TooBig { size: bits, len });
+        }
+        Ok(())
+    }
+
+    /// Writes the low `bits` bits of `value` without checking there is room for them
+    fn put(&mut self, value: u64, bits: u32) {
+        let mut done = 0;
+        while done < bits {
+            let byte = &mut self.bytes[self.position / 8];
+            let offset = (self.position % 8) as u32;
+            let take = (8 - offset).min(bits - done);
+            let (chunk, shift) = match self.order {
+                BitOrder::Msb => (
+                    (value >> (bits - done - take)) & mask(take),
+                    8 - offset - take,
+                ),
+                BitOrder::Lsb => ((value >> done) & mask(take), offset),
+            };
+            let clear = !((mask(take) as u8) << shift);
+            *byte = (*byte & clear) | ((chunk as u8) << shift);
+            done += take;
+            self.position += take as usize;
+        }
+    }
+
+    /// Writes `value` as an unsigned integer `bits` wide, at most 64, and advances past it
+    pub fn write_bits(&mut self, value: u64, bits: u32) -> error::Result<()> {
+        check_width(bits)?;
+        if value & !mask(bits) != 0 {
+            return Err(Error::BadInput {
+                size: bits as usize,
+                msg: "value doesn't fit in the bits",
+            });
+        }
+        self.ensure(bits as usize)?;
+        self.put(value, bits);
+        Ok(())
+    }
+
+    /// Writes `value` as a two's complement integer `bits` wide, at most 64, and advances past it
+    pub fn write_signed_bits(&mut self, value: i64, bits: u32) -> error::Result<()> {
+        check_width(bits)?;
+        let fits = match bits {
+            0 => value == 0,
+            64 => true,
+            _ => {
+                let bound = 1i64 << (bits - 1);
+                -bound <= value && value < bound
+            }
+        };
+        if !fits {
+            return Err(Error::BadInput {
+                size: bits as usize,
+                msg: "value doesn't fit in the bits",
+            });
+        }
+        self.write_bits(value as u64 & mask(bits), bits)
+    }
+
+    /// Writes a single bit
+    #[inline]
+    pub fn write_bool(&mut self, value: bool) -> error::Result<()> {
+        self.write_bits(u64::from(value), 1)
+    }
+
+    /// Writes `value` as an unsigned Exp-Golomb code, the `ue(v)` of H.264
+    pub fn write_exp_golomb(&mut self, value: u64) -> error::Result<()> {
+        let code = value.checked_add(1).ok_or(Error::BadInput {
+            size: 64,
+            msg: "value is too large for an exp-golomb code",
+        })?;
+        let zeros = 63 - code.leading_zeros();
+        self.ensure(2 * zeros as usize + 1)?;
+        self.put(0, zeros);
+        self.put(1, 1);
+        self.put(code & mask(zeros), zeros);
+        Ok(())
+    }
+
+    /// Writes `value` as a signed Exp-Golomb code, the `se(v)` of H.264
+    pub fn write_signed_exp_golomb(&mut self, value: i64) -> error::Result<()> {
+        let code = if value > 0 {
+            Some(value as u64 * 2 - 1)
+        } else {
+            value.unsigned_abs().checked_mul(2)
+        };
+        let code = code.ok_or(Error::BadInput {
+            size: 64,
+            msg: "value is too large for an exp-golomb code",
+        })?;
+        self.write_exp_golomb(code)
+    }
+
+    /// Writes the bytes of `n` one after the other from the current bit position on, e.g. a `u32`
+    /// in the given byte order; `N` is at most 16 bytes
+    pub fn write_with<N: IntoCtx<Endian> + SizeWith<Endian>>(
+        &mut self,
+        n: N,
+        ctx: Endian,
+    ) -> error::Result<()> {
+        let size = N::size_with(&ctx);
+        if size > MAX_BYTES {
+            return Err(Error::BadInput {
+                size,
+                msg: "values written as bits can be at most 16 bytes",
+            });
+        }
+        self.ensure(size * 8)?;
+        let mut buf = [0u8; MAX_BYTES];
+        n.into_ctx(&mut buf[..size], ctx);
+        for byte in &buf[..size] {
+            self.put(u64::from(*byte), 8);
+        }
+        Ok(())
+    }
+
+    /// Advances the writer to the start of the next byte, writing zeros, if it isn't already at
+    /// one
+    pub fn align_to_byte(&mut self) -> error::Result<()> {
+        let padding = self.position.next_multiple_of(8) - self.position;
+        self.ensure(padding)?;
+        self.put(0, padding as u32);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BE, LE};
+
+    #[test]
+    fn bit_reader_orders() {
+        let bytes = [0b1011_0010, 0b0110_1101];
+        let mut msb = BitReader::new(&bytes[..], BitOrder::Msb);
+        assert_eq!(msb.read_bits(3).unwrap(), 0b101);
+        assert_eq!(msb.read_bits(9).unwrap(), 0b1_0010_0110);
+        assert_eq!(msb.peek_bits(4).unwrap(), 0b1101);
+        assert_eq!(msb.read_signed_bits(4).unwrap(), -3);
+        assert!(msb.read_bool().is_err());
+
+        let mut lsb = BitReader::new(&bytes[..], BitOrder::Lsb);
+        assert_eq!(lsb.read_bits(3).unwrap(), 0b010);
+        assert_eq!(lsb.read_bits(9).unwrap(), 0b1_1011_0110);
+        assert_eq!(lsb.read_signed_bits(4).unwrap(), 6);
+
+        let mut full = BitReader::new(&[0xff; 9][..], BitOrder::Msb);
+        full.skip(4).unwrap();
+        assert_eq!(full.read_bits(64).unwrap(), u64::MAX);
+        assert!(full.read_bits(65).is_err());
+    }
+
+    #[test]
+    fn bit_reader_errors() {
+        let bytes = [0u8, 0x01];
+        let mut reader = BitReader::new(&bytes[..], BitOrder::Msb);
+        reader.skip(3).unwrap();
+        assert!(matches!(
+            reader.read_bits(14),
+            Err(Error::TooBig { size: 14, len: 13 })
+        ));
+        assert_eq!(reader.position(), 3);
+        assert!(matches!(
+            reader.read_with::<u16>(BE),
+            Err(Error::TooBig { size: 16, len: 13 })
+        ));
+        // too many leading zeros for the bits that are left
+        reader.skip(4).unwrap();
+        assert!(reader.read_exp_golomb().is_err());
+        assert_eq!(reader.position(), 7);
+        reader.align_to_byte().unwrap();
+        assert_eq!(reader.position(), 8);
+        assert!(
+            BitReader::new(&[0u8; 9][..], BitOrder::Msb)
+                .read_exp_golomb()
+                .is_err()
+        );
+        reader.set_position(17);
+        assert!(reader.align_to_byte().is_err());
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn bit_writer_round_trip() {
+        for order in [BitOrder::Msb, BitOrder::Lsb] {
+            let mut bytes = [0xffu8; 128];
+            let mut writer = BitWriter::new(&mut bytes[..], order);
+            writer.write_bits(0b101, 3).unwrap();
+            writer.write_signed_bits(-5, 7).unwrap();
+            writer.write_bool(false).unwrap();
+            writer.write_with(0xdeadbeefu32, LE).unwrap();
+            writer.write_bits(u64::MAX, 64).unwrap();
+            for value in [0, 1, 2, 7, 255, u64::MAX - 1] {
+                writer.write_exp_golomb(value).unwrap();
+            }
+            for value in [0, 1, -1, 17, -17, i64::MAX, i64::MIN + 1] {
+                writer.write_signed_exp_golomb(value).unwrap();
+            }
+            writer.align_to_byte().unwrap();
+            let len = writer.position();
+            assert!(writer.write_bits(2, 1).is_err());
+            assert!(writer.write_signed_bits(4, 3).is_err());
+            assert!(writer.write_exp_golomb(u64::MAX).is_err());
+            assert!(writer.write_signed_exp_golomb(i64::MIN).is_err());
+            assert_eq!(writer.position(), len);
+
+            let mut reader = BitReader::new(&bytes[..len / 8], order);
+            assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+            assert_eq!(reader.read_signed_bits(7).unwrap(), -5);
+            assert!(!reader.read_bool().unwrap());
+            assert_eq!(reader.read_with::<u32>(LE).unwrap(), 0xdeadbeef);
+            assert_eq!(reader.read_bits(64).unwrap(), u64::MAX);
+            for value in [0, 1, 2, 7, 255, u64::MAX - 1] {
+                assert_eq!(reader.read_exp_golomb().unwrap(), value);
+            }
+            for value in [0, 1, -1, 17, -17, i64::MAX, i64::MIN + 1] {
+                assert_eq!(reader.read_signed_exp_golomb().unwrap(), value);
+            }
+            reader.align_to_byte().unwrap();
+            assert_eq!(reader.remaining(), 0);
+            // the bytes after the last one written are untouched
+            assert_eq!(bytes[len / 8], 0xff);
+        }
+    }
+
+    #[test]
+    fn bit_writer_keeps_other_bits() {
+        let mut bytes = [0b1000_0001u8];
+        let mut writer = BitWriter::new(&mut bytes[..], BitOrder::Msb);
+        writer.set_position(2);
+        writer.write_bits(0b1010, 4).unwrap();
+        assert!(matches!(
+            writer.write_bits(0, 3),
+            Err(Error::TooBig { size: 3, len: 2 })
+        ));
+        assert_eq!(bytes, [0b1010_1001]);
+    }
+}
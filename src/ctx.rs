@@ -180,11 +180,12 @@
 //! }
 //! ```
 
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, ffi::CString, vec::Vec};
+use core::ffi::CStr;
 use core::mem::{MaybeUninit, size_of};
 use core::ptr::copy_nonoverlapping;
 use core::{result, str};
-#[cfg(feature = "std")]
-use std::ffi::{CStr, CString};
 
 use crate::endian::Endian;
 use crate::{Pread, Pwrite, error};
@@ -235,6 +236,37 @@ impl Default for StrCtx {
     }
 }
 
+/// The parsing context for reading a runtime counted collection like a `Vec<T>`: how many elements
+/// to read, and the context to read each element with
+///
+/// # Example
+/// ```rust
+/// ##[cfg(feature = "alloc")] {
+/// use scroll::{ctx::CountCtx, Pread, BE};
+/// let bytes = [0x00, 0x02, 0xde, 0xad, 0xbe, 0xef];
+/// let offset = &mut 0;
+/// let count: u16 = bytes.gread_with(offset, BE).unwrap();
+/// let table: Vec<u16> = bytes.gread_with(offset, CountCtx::new(count as usize, BE)).unwrap();
+/// assert_eq!(table, [0xdead, 0xbeef]);
+/// assert_eq!(*offset, 6);
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct CountCtx<Ctx = ()> {
+    /// The number of elements to read
+    pub count: usize,
+    /// The context each element is read with
+    pub ctx: Ctx,
+}
+
+impl<Ctx> CountCtx<Ctx> {
+    /// A context for reading `count` elements, each with `ctx`
+    #[inline]
+    pub fn new(count: usize, ctx: Ctx) -> Self {
+        CountCtx { count, ctx }
+    }
+}
+
 impl StrCtx {
     pub fn len(&self) -> usize {
         match self {
@@ -260,7 +292,7 @@ pub trait FromCtx<Ctx: Copy = (), This: ?Sized = [u8]> {
 /// `[u8]`), then you need to implement this trait
 ///
 /// ```rust
-/// ##[cfg(feature = "std")] {
+/// ##[cfg(feature = "alloc")] {
 /// use scroll::{self, ctx, Pread};
 /// #[derive(Debug, PartialEq, Eq)]
 /// pub struct Foo(u16);
@@ -328,12 +360,24 @@ pub trait FromCtx<Ctx: Copy = (), This: ?Sized = [u8]> {
 /// let bytes: [u8; 4] = [0xde, 0xad, 0, 0];
 /// let foo: Result<Foo, ExternalError> = bytes.pread(0);
 /// ```
+///
+/// The `Pread` and `Pwrite` derives do the same with a `#[scroll(error = ExternalError)]` on the
+/// type, which only requires `ExternalError: From<scroll::Error>`.
 pub trait TryFromCtx<'a, Ctx: Copy = (), This: ?Sized = [u8]>
 where
     Self: 'a + Sized,
 {
     type Error;
     fn try_from_ctx(from: &'a This, ctx: Ctx) -> Result<(Self, usize), Self::Error>;
+    /// Shifts the offsets an error of `try_from_ctx` refers to by `offset`, where `Self` started
+    /// in the enclosing input; [Pread](../trait.Pread.html) calls this, so that an
+    /// [`Error::Context`](../enum.Error.html#variant.Context) is relative to the whole buffer. By
+    /// default the error is returned unchanged.
+    #[inline]
+    fn offset_error(error: Self::Error, offset: usize) -> Self::Error {
+        let _ = offset;
+        error
+    }
 }
 
 /// Writes `Self` into `This` using the context `Ctx`
@@ -345,7 +389,7 @@ pub trait IntoCtx<Ctx: Copy = (), This: ?Sized = [u8]>: Sized {
 /// To implement writing into an arbitrary byte buffer, implement `TryIntoCtx`
 /// # Example
 /// ```rust
-/// ##[cfg(feature = "std")] {
+/// ##[cfg(feature = "alloc")] {
 /// use scroll::{self, ctx, LE, Endian, Pwrite};
 /// #[derive(Debug, PartialEq, Eq)]
 /// pub struct Foo(u16);
@@ -370,6 +414,13 @@ pub trait IntoCtx<Ctx: Copy = (), This: ?Sized = [u8]>: Sized {
 pub trait TryIntoCtx<Ctx: Copy = (), This: ?Sized = [u8]>: Sized {
     type Error;
     fn try_into_ctx(self, _: &mut This, ctx: Ctx) -> Result<usize, Self::Error>;
+    /// Shifts the offsets an error of `try_into_ctx` refers to by `offset`, where `Self` started
+    /// in the enclosing output; see [`TryFromCtx::offset_error`].
+    #[inline]
+    fn offset_error(error: Self::Error, offset: usize) -> Self::Error {
+        let _ = offset;
+        error
+    }
 }
 
 /// Gets the size of `Self` with a `Ctx`, and in `Self::Units`. Implementors can then call `Gread` related functions
@@ -710,7 +761,7 @@ where
     }
 }
 
-impl<'a> TryIntoCtx for &'a [u8] {
+impl TryIntoCtx for &[u8] {
     type Error = error::Error;
     #[inline]
     fn try_into_ctx(self, dst: &mut [u8], _ctx: ()) -> error::Result<usize> {
@@ -732,7 +783,7 @@ impl<'a> TryIntoCtx for &'a [u8] {
 }
 
 // TODO: make TryIntoCtx use StrCtx for awesomeness
-impl<'a> TryIntoCtx for &'a str {
+impl TryIntoCtx for &str {
     type Error = error::Error;
     #[inline]
     fn try_into_ctx(self, dst: &mut [u8], _ctx: ()) -> error::Result<usize> {
@@ -803,7 +854,7 @@ impl<'a, Ctx: Copy, T: TryFromCtx<'a, Ctx, Error = error::Error>, const N: usize
                     *element = MaybeUninit::new(val);
                 }
                 Err(e) => {
-                    error_ctx = Some((e, idx));
+                    error_ctx = Some((e.at_index(idx, offset), idx));
                     break;
                 }
             }
@@ -823,6 +874,10 @@ impl<'a, Ctx: Copy, T: TryFromCtx<'a, Ctx, Error = error::Error>, const N: usize
             Ok((buf.map(|element| unsafe { element.assume_init() }), offset))
         }
     }
+    #[inline]
+    fn offset_error(error: Self::Error, offset: usize) -> Self::Error {
+        error.offset_by(offset)
+    }
 }
 impl<Ctx: Copy, T: TryIntoCtx<Ctx, Error = error::Error>, const N: usize> TryIntoCtx<Ctx>
     for [T; N]
@@ -830,11 +885,16 @@ impl<Ctx: Copy, T: TryIntoCtx<Ctx, Error = error::Error>, const N: usize> TryInt
     type Error = error::Error;
     fn try_into_ctx(self, buf: &mut [u8], ctx: Ctx) -> Result<usize, Self::Error> {
         let mut offset = 0;
-        for element in self {
-            buf.gwrite_with(element, &mut offset, ctx)?;
+        for (idx, element) in self.into_iter().enumerate() {
+            buf.gwrite_with(element, &mut offset, ctx)
+                .map_err(|e| e.at_index(idx, offset))?;
         }
         Ok(offset)
     }
+    #[inline]
+    fn offset_error(error: Self::Error, offset: usize) -> Self::Error {
+        error.offset_by(offset)
+    }
 }
 impl<Ctx, T: SizeWith<Ctx>, const N: usize> SizeWith<Ctx> for [T; N] {
     fn size_with(ctx: &Ctx) -> usize {
@@ -842,7 +902,109 @@ impl<Ctx, T: SizeWith<Ctx>, const N: usize> SizeWith<Ctx> for [T; N] {
     }
 }
 
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
+impl<'a, Ctx: Copy, T: TryFromCtx<'a, Ctx>> TryFromCtx<'a, CountCtx<Ctx>> for Vec<T>
+where
+    T::Error: From<error::Error>,
+{
+    type Error = T::Error;
+    fn try_from_ctx(src: &'a [u8], ctx: CountCtx<Ctx>) -> Result<(Self, usize), Self::Error> {
+        let offset = &mut 0;
+        // the count usually comes from the input, so don't trust it for the allocation
+        let mut vec = Vec::with_capacity(ctx.count.min(src.len()));
+        for _ in 0..ctx.count {
+            let start = *offset;
+            vec.push(src.gread_with::<T>(offset, ctx.ctx)?);
+            // elements which take up no bytes never run out of input, so the count can't be
+            // larger than the input, lest a bogus one keeps this loop going for ever
+            if *offset == start && ctx.count > src.len() {
+                return Err(error::Error::BadInput {
+                    size: ctx.count,
+                    msg: "count of empty elements exceeds the input",
+                }
+                .into());
+            }
+        }
+        Ok((vec, *offset))
+    }
+    #[inline]
+    fn offset_error(error: Self::Error, offset: usize) -> Self::Error {
+        T::offset_error(error, offset)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, Ctx: Copy, T: TryFromCtx<'a, Ctx>> TryFromCtx<'a, CountCtx<Ctx>> for Box<[T]>
+where
+    T::Error: From<error::Error>,
+{
+    type Error = T::Error;
+    #[inline]
+    fn try_from_ctx(src: &'a [u8], ctx: CountCtx<Ctx>) -> Result<(Self, usize), Self::Error> {
+        let (vec, size) = Vec::<T>::try_from_ctx(src, ctx)?;
+        Ok((vec.into_boxed_slice(), size))
+    }
+    #[inline]
+    fn offset_error(error: Self::Error, offset: usize) -> Self::Error {
+        T::offset_error(error, offset)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Ctx: Copy, T: TryIntoCtx<Ctx>> TryIntoCtx<Ctx> for Vec<T>
+where
+    T::Error: From<error::Error>,
+{
+    type Error = T::Error;
+    fn try_into_ctx(self, buf: &mut [u8], ctx: Ctx) -> Result<usize, Self::Error> {
+        let offset = &mut 0;
+        for element in self {
+            buf.gwrite_with(element, offset, ctx)?;
+        }
+        Ok(*offset)
+    }
+    #[inline]
+    fn offset_error(error: Self::Error, offset: usize) -> Self::Error {
+        T::offset_error(error, offset)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, Ctx: Copy, T> TryIntoCtx<Ctx> for &'a Vec<T>
+where
+    &'a T: TryIntoCtx<Ctx>,
+    <&'a T as TryIntoCtx<Ctx>>::Error: From<error::Error>,
+{
+    type Error = <&'a T as TryIntoCtx<Ctx>>::Error;
+    fn try_into_ctx(self, buf: &mut [u8], ctx: Ctx) -> Result<usize, Self::Error> {
+        let offset = &mut 0;
+        for element in self {
+            buf.gwrite_with(element, offset, ctx)?;
+        }
+        Ok(*offset)
+    }
+    #[inline]
+    fn offset_error(error: Self::Error, offset: usize) -> Self::Error {
+        <&'a T>::offset_error(error, offset)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Ctx: Copy, T: TryIntoCtx<Ctx>> TryIntoCtx<Ctx> for Box<[T]>
+where
+    T::Error: From<error::Error>,
+{
+    type Error = T::Error;
+    #[inline]
+    fn try_into_ctx(self, buf: &mut [u8], ctx: Ctx) -> Result<usize, Self::Error> {
+        Vec::from(self).try_into_ctx(buf, ctx)
+    }
+    #[inline]
+    fn offset_error(error: Self::Error, offset: usize) -> Self::Error {
+        T::offset_error(error, offset)
+    }
+}
+
 impl<'a> TryFromCtx<'a> for &'a CStr {
     type Error = error::Error;
     #[inline]
@@ -855,18 +1017,17 @@ impl<'a> TryFromCtx<'a> for &'a CStr {
     }
 }
 
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
 impl<'a> TryFromCtx<'a> for CString {
     type Error = error::Error;
     #[inline]
     fn try_from_ctx(src: &'a [u8], _ctx: ()) -> result::Result<(Self, usize), Self::Error> {
         let (raw, bytes_read) = <&CStr as TryFromCtx>::try_from_ctx(src, _ctx)?;
-        Ok((raw.to_owned(), bytes_read))
+        Ok((CString::from(raw), bytes_read))
     }
 }
 
-#[cfg(feature = "std")]
-impl<'a> TryIntoCtx for &'a CStr {
+impl TryIntoCtx for &CStr {
     type Error = error::Error;
     #[inline]
     fn try_into_ctx(self, dst: &mut [u8], _ctx: ()) -> error::Result<usize> {
@@ -874,7 +1035,7 @@ impl<'a> TryIntoCtx for &'a CStr {
     }
 }
 
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
 impl TryIntoCtx for CString {
     type Error = error::Error;
     #[inline]
@@ -896,9 +1057,10 @@ impl TryIntoCtx for CString {
 // }
 
 #[cfg(test)]
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
 mod tests {
     use super::*;
+    use alloc::vec;
 
     #[test]
     fn parse_a_cstr() {
@@ -926,4 +1088,51 @@ mod tests {
         assert_eq!(bytes_read, as_bytes.len());
         assert_eq!(got, src);
     }
+
+    #[test]
+    fn round_trip_a_vec() {
+        use crate::{BE, LE};
+        let bytes = [0xde, 0xad, 0xbe, 0xef, 0x13, 0x37];
+        let offset = &mut 0;
+        let vec: Vec<u16> = bytes.gread_with(offset, CountCtx::new(3, BE)).unwrap();
+        assert_eq!(vec, [0xdead, 0xbeef, 0x1337]);
+        assert_eq!(*offset, 6);
+
+        let mut buffer = [0u8; 6];
+        assert_eq!(buffer.pwrite_with(&vec, 0, BE).unwrap(), 6);
+        assert_eq!(buffer, bytes);
+        assert_eq!(buffer.pwrite_with(&vec, 0, LE).unwrap(), 6);
+        assert_eq!(buffer[..4], [0xad, 0xde, 0xef, 0xbe]);
+        assert_eq!(buffer.pwrite_with(vec, 0, BE).unwrap(), 6);
+        assert_eq!(buffer, bytes);
+
+        let boxed: Box<[u8]> = bytes.pread_with(1, CountCtx::new(2, LE)).unwrap();
+        assert_eq!(&*boxed, &[0xad, 0xbe]);
+        let empty: Vec<u32> = bytes.pread_with(6, CountCtx::new(0, LE)).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn vec_count_too_large() {
+        use crate::LE;
+        let bytes = [0u8; 6];
+        let res = bytes.pread_with::<Vec<u32>>(0, CountCtx::new(usize::MAX, LE));
+        assert!(res.is_err());
+        let mut buffer = [0u8; 3];
+        assert!(buffer.pwrite_with(vec![1u16, 2], 0, LE).is_err());
+        // elements which take up no bytes
+        let empty: Vec<[u8; 0]> = bytes.pread_with(0, CountCtx::new(6, LE)).unwrap();
+        assert_eq!(empty.len(), 6);
+        let res = bytes.pread_with::<Vec<[u8; 0]>>(0, CountCtx::new(usize::MAX, LE));
+        assert!(matches!(res, Err(error::Error::BadInput { .. })));
+    }
+
+    #[test]
+    fn vec_of_strs() {
+        let bytes = b"foo\0bar\0baz";
+        let strs: Vec<&str> = bytes
+            .pread_with(0, CountCtx::new(2, StrCtx::default()))
+            .unwrap();
+        assert_eq!(strs, ["foo", "bar"]);
+    }
 }
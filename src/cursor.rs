@@ -0,0 +1,210 @@
+use crate::ctx::TryFromCtx;
+use crate::endian::Endian;
+use crate::error::{self, Error};
+use crate::pread::Pread;
+
+/// A byte slice together with the current read position, so that parsers don't have to thread an
+/// `offset: &mut usize` through every [gread_with](trait.Pread.html#method.gread_with) call.
+///
+/// A `Cursor` also carries a default context, which [read](#method.read) and
+/// [peek](#method.peek) use; [Cursor::new](#method.new) defaults to the native [Endian](enum.Endian.html).
+/// Reads follow the semantics of `Pread` for `[u8]`: they advance the position by the size
+/// `TryFromCtx` reports, and reading at the end of the slice reads from an empty slice.
+///
+/// A failed read leaves the position untouched.
+///
+/// # Example
+/// ```rust
+/// use scroll::{ctx::StrCtx, Cursor, BE};
+/// let bytes = b"\x00\x02hi\x00\x00\x00\x00\xde\xad\xbe\xef";
+/// let mut cursor = Cursor::with_ctx(&bytes[..], BE);
+/// let len = cursor.read::<u16>().unwrap();
+/// let name: &str = cursor.read_with(StrCtx::Length(len as usize)).unwrap();
+/// assert_eq!(name, "hi");
+/// cursor.align_to(4).unwrap();
+/// cursor.skip(4).unwrap();
+/// assert_eq!(cursor.peek::<u16>().unwrap(), 0xdead);
+/// assert_eq!(cursor.read::<u32>().unwrap(), 0xdeadbeef);
+/// assert_eq!(cursor.remaining(), 0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cursor<'a, Ctx = Endian> {
+    bytes: &'a [u8],
+    position: usize,
+    ctx: Ctx,
+}
+
+impl<'a> Cursor<'a> {
+    /// Creates a cursor at the start of `bytes`, reading with the native endianness by default
+    #[inline]
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self::with_ctx(bytes, Endian::default())
+    }
+}
+
+impl<'a, Ctx: Copy> Cursor<'a, Ctx> {
+    /// Creates a cursor at the start of `bytes`, reading with `ctx` by default
+    #[inline]
+    pub fn with_ctx(bytes: &'a [u8], ctx: Ctx) -> Self {
+        Cursor {
+            bytes,
+            position: 0,
+            ctx,
+        }
+    }
+
+    /// The current position
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves the cursor to `position`; it is checked by the next read
+    #[inline]
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// The default context of this cursor
+    #[inline]
+    pub fn ctx(&self) -> Ctx {
+        self.ctx
+    }
+
+    /// The whole underlying slice
+    #[inline]
+    pub fn get_ref(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// The number of bytes left after the current position
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.bytes.len().saturating_sub(self.position)
+    }
+
+    /// The bytes left after the current position
+    #[inline]
+    pub fn remaining_bytes(&self) -> &'a [u8] {
+        self.bytes.get(self.position..).unwrap_or_default()
+    }
+
+    /// Whether there are no bytes left to read
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Reads an `N` with the cursor's context, and advances past it
+    #[inline]
+    pub fn read<N: TryFromCtx<'a, Ctx>>(&mut self) -> Result<N, N::Error>
+    where
+        N::Error: From<Error>,
+    {
+        self.read_with(self.ctx)
+    }
+
+    /// Reads an `N` with the given `ctx`, and advances past it
+    #[inline]
+    pub fn read_with<N: TryFromCtx<'a, C>, C: Copy>(&mut self, ctx: C) -> Result<N, N::Error>
+    where
+        N::Error: From<Error>,
+    {
+        self.bytes.gread_with(&mut self.position, ctx)
+    }
+
+    /// Reads an `N` with the cursor's context, without advancing
+    #[inline]
+    pub fn peek<N: TryFromCtx<'a, Ctx>>(&self) -> Result<N, N::Error>
+    where
+        N::Error: From<Error>,
+    {
+        self.peek_with(self.ctx)
+    }
+
+    /// Reads an `N` with the given `ctx`, without advancing
+    #[inline]
+    pub fn peek_with<N: TryFromCtx<'a, C>, C: Copy>(&self, ctx: C) -> Result<N, N::Error>
+    where
+        N::Error: From<Error>,
+    {
+        self.bytes.pread_with(self.position, ctx)
+    }
+
+    /// Advances the cursor by `count` bytes
+    pub fn skip(&mut self, count: usize) -> error::Result<()> {
+        let len = self.remaining();
+        if count > len {
+            return Err(Error::TooBig { size: count, len });
+        }
+        self.position += count;
+        Ok(())
+    }
+
+    /// Advances the cursor to the next multiple of `alignment`, if it isn't already on one
+    pub fn align_to(&mut self, alignment: usize) -> error::Result<()> {
+        let aligned = self
+            .position
+            .checked_next_multiple_of(alignment)
+            .ok_or(Error::BadInput {
+                size: alignment,
+                msg: "invalid alignment",
+            })?;
+        if aligned > self.bytes.len() {
+            return Err(Error::BadOffset(aligned));
+        }
+        self.position = aligned;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BE, LE};
+
+    #[test]
+    fn cursor_read() {
+        let bytes = [0x01, 0x00, 0x02, 0x03, 0x04];
+        let mut cursor = Cursor::with_ctx(&bytes[..], LE);
+        assert_eq!(cursor.read::<u16>().unwrap(), 1);
+        assert_eq!(cursor.peek_with::<u16, _>(BE).unwrap(), 0x0203);
+        assert_eq!(cursor.position(), 2);
+        assert_eq!(cursor.read_with::<u16, _>(BE).unwrap(), 0x0203);
+        assert!(matches!(
+            cursor.read::<u16>(),
+            Err(Error::TooBig { size: 2, len: 1 })
+        ));
+        // a failed read doesn't move the cursor
+        assert_eq!(cursor.position(), 4);
+        assert_eq!(cursor.remaining_bytes(), [0x04]);
+        assert_eq!(cursor.read::<u8>().unwrap(), 4);
+        assert!(cursor.is_empty());
+        let empty: &[u8] = cursor.read_with(0).unwrap();
+        assert!(empty.is_empty());
+        cursor.set_position(6);
+        assert!(matches!(cursor.read::<u8>(), Err(Error::BadOffset(6))));
+        assert_eq!(cursor.remaining(), 0);
+        assert!(cursor.remaining_bytes().is_empty());
+    }
+
+    #[test]
+    fn cursor_skip_and_align() {
+        let bytes = [0u8; 10];
+        let mut cursor = Cursor::new(&bytes[..]);
+        cursor.skip(3).unwrap();
+        cursor.align_to(4).unwrap();
+        assert_eq!(cursor.position(), 4);
+        cursor.align_to(4).unwrap();
+        assert_eq!(cursor.position(), 4);
+        assert!(cursor.align_to(0).is_err());
+        cursor.align_to(8).unwrap();
+        assert!(matches!(cursor.align_to(16), Err(Error::BadOffset(16))));
+        assert!(matches!(
+            cursor.skip(3),
+            Err(Error::TooBig { size: 3, len: 2 })
+        ));
+        cursor.skip(2).unwrap();
+        assert_eq!(cursor.position(), 10);
+    }
+}
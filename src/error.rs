@@ -1,3 +1,5 @@
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, string::String, vec::Vec};
 use core::fmt::{self, Display};
 use core::{error, result};
 #[cfg(feature = "std")]
@@ -18,12 +20,175 @@ pub enum Error {
         msg: &'static str,
     },
     /// A custom Scroll error for reporting messages to clients.
-    /// For no-std, use [`Error::BadInput`] with a static string.
-    #[cfg(feature = "std")]
+    /// Without `alloc`, use [`Error::BadInput`] with a static string.
+    #[cfg(feature = "alloc")]
     Custom(String),
     /// Returned when IO based errors are encountered
     #[cfg(feature = "std")]
     IO(io::Error),
+    /// Another error, along with where in the input it happened; see [`Error::in_field`]
+    #[cfg(feature = "alloc")]
+    Context(Box<ErrorContext>),
+}
+
+/// A step on the path from the outermost type to the place where an error happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// A named (or, for tuple structs, numbered) field
+    Field(&'static str),
+    /// An element of an array or collection
+    Index(usize),
+}
+
+/// Where an [`Error::Context`] happened: the offset, the type and the path of fields leading to
+/// the failure
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct ErrorContext {
+    offset: usize,
+    type_name: Option<&'static str>,
+    // innermost segment first, as that is the order they are pushed in
+    path: Vec<PathSegment>,
+    error: Error,
+}
+
+#[cfg(feature = "alloc")]
+impl ErrorContext {
+    /// The offset where the failing read or write started, relative to the buffer the outermost
+    /// value was read from or written to (or the [Window](struct.Window.html) it was read through)
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+    /// The name of the outermost type which added context
+    pub fn type_name(&self) -> Option<&'static str> {
+        self.type_name
+    }
+    /// The path from the outermost type to the failure, outermost segment first
+    pub fn path(&self) -> impl Iterator<Item = PathSegment> + '_ {
+        self.path.iter().rev().copied()
+    }
+    /// The error which happened
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Display for ErrorContext {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(type_name) = self.type_name {
+            write!(fmt, "{type_name}")?;
+        }
+        for (i, segment) in self.path().enumerate() {
+            match segment {
+                PathSegment::Field(field) if i == 0 && self.type_name.is_none() => {
+                    write!(fmt, "{field}")?
+                }
+                PathSegment::Field(field) => write!(fmt, ".{field}")?,
+                PathSegment::Index(index) => write!(fmt, "[{index}]")?,
+            }
+        }
+        write!(fmt, " at {:#x}: {}", self.offset, self.error)
+    }
+}
+
+impl Error {
+    /// Records that this error happened while reading or writing `field` of `type_name`, which
+    /// started at `offset` in that type.
+    ///
+    /// Called from the outside in as the error propagates, this builds up a breadcrumb trail like
+    /// `Header.sections[3].name at 0x1f0`; the derive macros do so for every field. An error which
+    /// already has a context keeps its offset, that of the innermost failure, which
+    /// [Pread](trait.Pread.html) and [Pwrite](trait.Pwrite.html) shift by the offset they read or
+    /// wrote the value at (see [`TryFromCtx::offset_error`](ctx/trait.TryFromCtx.html#method.offset_error)).
+    /// Without the `alloc` feature this returns the error unchanged.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "alloc")] {
+    /// use scroll::Error;
+    /// let err = Error::BadOffset(4)
+    ///     .in_field("Section", "name", 0x10)
+    ///     // the section was read at 0x1a0 of the sections, which are at 0x40 of the header
+    ///     .offset_by(0x1a0)
+    ///     .at_index(3, 0x1a0)
+    ///     .offset_by(0x40)
+    ///     .in_field("Header", "sections", 0x40);
+    /// assert_eq!(err.to_string(), "Header.sections[3].name at 0x1f0: bad offset 4");
+    /// assert!(matches!(err.root_cause(), Error::BadOffset(4)));
+    /// # }
+    /// ```
+    #[inline]
+    pub fn in_field(self, type_name: &'static str, field: &'static str, offset: usize) -> Error {
+        #[cfg(feature = "alloc")]
+        {
+            let mut context = self.into_context(offset);
+            context.type_name = Some(type_name);
+            context.path.push(PathSegment::Field(field));
+            Error::Context(context)
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            let _ = (type_name, field, offset);
+            self
+        }
+    }
+
+    /// Records that this error happened while reading or writing element `index`, which started
+    /// at `offset` in its collection; see [`Error::in_field`].
+    #[inline]
+    pub fn at_index(self, index: usize, offset: usize) -> Error {
+        #[cfg(feature = "alloc")]
+        {
+            let mut context = self.into_context(offset);
+            context.path.push(PathSegment::Index(index));
+            Error::Context(context)
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            let _ = (index, offset);
+            self
+        }
+    }
+
+    /// Shifts the offset of an [`Error::Context`] by `offset`, e.g. to make it relative to an
+    /// enclosing buffer; other errors are returned unchanged
+    #[inline]
+    pub fn offset_by(self, offset: usize) -> Error {
+        #[cfg(not(feature = "alloc"))]
+        let _ = offset;
+        match self {
+            #[cfg(feature = "alloc")]
+            Error::Context(mut context) => {
+                context.offset = context.offset.saturating_add(offset);
+                Error::Context(context)
+            }
+            err => err,
+        }
+    }
+
+    /// The error underneath any [`Error::Context`]
+    pub fn root_cause(&self) -> &Error {
+        match self {
+            #[cfg(feature = "alloc")]
+            Error::Context(context) => context.error.root_cause(),
+            err => err,
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn into_context(self, offset: usize) -> Box<ErrorContext> {
+        match self {
+            // it already has the offset of the innermost failure
+            Error::Context(context) => context,
+            error => Box::new(ErrorContext {
+                offset,
+                type_name: None,
+                path: Vec::new(),
+                error,
+            }),
+        }
+    }
 }
 
 impl error::Error for Error {
@@ -32,10 +197,12 @@ impl error::Error for Error {
             Error::TooBig { .. } => "TooBig",
             Error::BadOffset(_) => "BadOffset",
             Error::BadInput { .. } => "BadInput",
-            #[cfg(feature = "std")]
+            #[cfg(feature = "alloc")]
             Error::Custom(_) => "Custom",
             #[cfg(feature = "std")]
             Error::IO(_) => "IO",
+            #[cfg(feature = "alloc")]
+            Error::Context(_) => "Context",
         }
     }
     fn cause(&self) -> Option<&dyn error::Error> {
@@ -43,10 +210,12 @@ impl error::Error for Error {
             Error::TooBig { .. } => None,
             Error::BadOffset(_) => None,
             Error::BadInput { .. } => None,
-            #[cfg(feature = "std")]
+            #[cfg(feature = "alloc")]
             Error::Custom(_) => None,
             #[cfg(feature = "std")]
             Error::IO(io) => io.source(),
+            #[cfg(feature = "alloc")]
+            Error::Context(context) => Some(&context.error),
         }
     }
 }
@@ -70,7 +239,7 @@ impl Display for Error {
             Error::BadInput { msg, size } => {
                 write!(fmt, "bad input {msg} ({size})")
             }
-            #[cfg(feature = "std")]
+            #[cfg(feature = "alloc")]
             Error::Custom(msg) => {
                 write!(fmt, "{msg}")
             }
@@ -78,8 +247,62 @@ impl Display for Error {
             Error::IO(err) => {
                 write!(fmt, "{err}")
             }
+            #[cfg(feature = "alloc")]
+            Error::Context(context) => {
+                write!(fmt, "{context}")
+            }
         }
     }
 }
 
 pub type Result<T> = result::Result<T, Error>;
+
+#[cfg(test)]
+#[cfg(feature = "alloc")]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn error_context() {
+        let err = Error::BadInput {
+            size: 1,
+            msg: "invalid tag",
+        }
+        .at_index(2, 8)
+        .offset_by(4)
+        .in_field("Table", "0", 4)
+        .offset_by(0x100);
+        assert_eq!(
+            err.to_string(),
+            "Table.0[2] at 0x10c: bad input invalid tag (1)"
+        );
+        let Error::Context(context) = &err else {
+            panic!("{err:?} has no context");
+        };
+        assert_eq!(context.offset(), 0x10c);
+        assert_eq!(context.type_name(), Some("Table"));
+        assert!(
+            context
+                .path()
+                .eq([PathSegment::Field("0"), PathSegment::Index(2)])
+        );
+        assert!(matches!(err.root_cause(), Error::BadInput { size: 1, .. }));
+        // without a type, the path starts with the field
+        let err = Error::BadOffset(3).at_index(1, 2);
+        assert_eq!(err.to_string(), "[1] at 0x2: bad offset 3");
+        // the innermost offset is kept
+        let err = Error::BadOffset(3).at_index(1, 2).in_field("Table", "0", 4);
+        assert_eq!(err.to_string(), "Table.0[1] at 0x2: bad offset 3");
+        // offsets saturate instead of overflowing
+        let Error::Context(context) = err.offset_by(usize::MAX) else {
+            panic!("no context");
+        };
+        assert_eq!(context.offset(), usize::MAX);
+        // offset_by only applies to context
+        assert!(matches!(
+            Error::BadOffset(3).offset_by(1),
+            Error::BadOffset(3)
+        ));
+    }
+}
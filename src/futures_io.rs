@@ -0,0 +1,120 @@
+//! Async counterparts of [IOread](../trait.IOread.html), [BufIOread](../trait.BufIOread.html) and
+//! [IOwrite](../trait.IOwrite.html) for the `AsyncRead`, `AsyncBufRead` and `AsyncWrite` streams
+//! of the `futures-io` crate, as used by `futures`, `async-std` and `smol`, enabled by the
+//! `futures-io` feature.
+//!
+//! # Example
+//! ```rust
+//! use scroll::futures_io::{AsyncBufIOread, AsyncIOread, AsyncIOwrite};
+//! use scroll::{Uleb128, BE};
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let mut bytes = Vec::new();
+//! bytes.iowrite_with(0xdeadbeefu32, BE).await.unwrap();
+//! bytes.try_iowrite(Uleb128::from(624485)).await.unwrap();
+//! let mut stream = &bytes[..];
+//! assert_eq!(stream.ioread_with::<u32>(BE).await.unwrap(), 0xdeadbeef);
+//! let n: Uleb128 = stream.try_ioread().await.unwrap();
+//! assert_eq!(u64::from(n), 624485);
+//! # });
+//! ```
+
+use core::future::poll_fn;
+use core::pin::Pin;
+use std::io::{self, Result};
+
+use ::futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+async fn read_exact<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    mut buf: &mut [u8],
+) -> Result<()> {
+    while !buf.is_empty() {
+        let n = poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, buf)).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf = &mut core::mem::take(&mut buf)[n..];
+    }
+    Ok(())
+}
+
+async fn write_all<W: AsyncWrite + Unpin + ?Sized>(writer: &mut W, mut buf: &[u8]) -> Result<()> {
+    while !buf.is_empty() {
+        let n = poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, buf)).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        buf = &buf[n..];
+    }
+    Ok(())
+}
+
+async_io_traits!("`futures-io`'s");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BE, LE, Uleb128};
+    use std::io::ErrorKind;
+    use std::task::{Context, Poll};
+
+    /// An in-memory stream handing out at most `chunk` bytes at a time
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        chunk: usize,
+    }
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize>> {
+            let n = self.chunk.min(buf.len()).min(self.bytes.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Poll::Ready(Ok(n))
+        }
+    }
+
+    impl AsyncBufRead for Trickle<'_> {
+        fn poll_fill_buf(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<&[u8]>> {
+            let this = self.get_mut();
+            let n = this.chunk.min(this.bytes.len());
+            Poll::Ready(Ok(&this.bytes[..n]))
+        }
+
+        fn consume(mut self: Pin<&mut Self>, amt: usize) {
+            self.bytes = &self.bytes[amt..];
+        }
+    }
+
+    #[::tokio::test]
+    async fn async_io_in_memory() {
+        let mut bytes = Vec::new();
+        bytes.iowrite_with(0xdeadbeefu32, BE).await.unwrap();
+        for n in [0, 128, u64::MAX] {
+            bytes.try_iowrite(Uleb128::from(n)).await.unwrap();
+        }
+        assert_eq!(bytes.try_iowrite(&[0xab; 8][..]).await.unwrap(), 8);
+        bytes.iowrite(0x80u8).await.unwrap();
+
+        let mut stream = Trickle {
+            bytes: &bytes,
+            chunk: 3,
+        };
+        assert_eq!(stream.ioread_with::<u32>(BE).await.unwrap(), 0xdeadbeef);
+        for n in [0, 128, u64::MAX] {
+            assert_eq!(u64::from(stream.try_ioread::<Uleb128>().await.unwrap()), n);
+        }
+        assert_eq!(
+            stream.ioread_with::<u64>(LE).await.unwrap(),
+            0xabab_abab_abab_abab
+        );
+        // the truncated Uleb128 at the end of the stream
+        assert!(stream.try_ioread::<Uleb128>().await.is_err());
+        let err = stream.ioread::<u8>().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -1,8 +1,8 @@
 use core::convert::{AsRef, From};
 use core::result;
 
-use crate::ctx::TryFromCtx;
-use crate::{Pread, error};
+use crate::ctx::{MeasureWith, TryFromCtx, TryIntoCtx};
+use crate::{Pread, Pwrite, error};
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 /// An unsigned leb128 integer
@@ -24,6 +24,21 @@ impl Uleb128 {
         *offset += tmp.size();
         Ok(tmp.into())
     }
+    #[inline]
+    /// Write `value` as a variable length u64 into `bytes` at `offset`, returning the number of bytes written
+    pub fn write(bytes: &mut [u8], offset: &mut usize, value: u64) -> error::Result<usize> {
+        bytes.gwrite(Uleb128::from(value), offset)
+    }
+}
+
+impl From<u64> for Uleb128 {
+    #[inline]
+    fn from(value: u64) -> Uleb128 {
+        Uleb128 {
+            value,
+            count: unsigned_size(u128::from(value)),
+        }
+    }
 }
 
 impl AsRef<u64> for Uleb128 {
@@ -59,6 +74,21 @@ impl Sleb128 {
         *offset += tmp.size();
         Ok(tmp.into())
     }
+    #[inline]
+    /// Write `value` as a variable length i64 into `bytes` at `offset`, returning the number of bytes written
+    pub fn write(bytes: &mut [u8], offset: &mut usize, value: i64) -> error::Result<usize> {
+        bytes.gwrite(Sleb128::from(value), offset)
+    }
+}
+
+impl From<i64> for Sleb128 {
+    #[inline]
+    fn from(value: i64) -> Sleb128 {
+        Sleb128 {
+            value,
+            count: signed_size(i128::from(value)),
+        }
+    }
 }
 
 impl AsRef<i64> for Sleb128 {
@@ -75,11 +105,11 @@ impl From<Sleb128> for i64 {
 }
 
 // Below implementation heavily adapted from: https://github.com/fitzgen/leb128
-const CONTINUATION_BIT: u8 = 1 << 7;
+pub(crate) const CONTINUATION_BIT: u8 = 1 << 7;
 const SIGN_BIT: u8 = 1 << 6;
 
 #[inline]
-fn mask_continuation(byte: u8) -> u8 {
+pub(crate) fn mask_continuation(byte: u8) -> u8 {
     byte & !CONTINUATION_BIT
 }
 
@@ -169,10 +199,383 @@ impl<'a> TryFromCtx<'a> for Sleb128 {
     }
 }
 
+impl<Ctx> MeasureWith<Ctx> for Uleb128 {
+    #[inline]
+    fn measure_with(&self, _ctx: &Ctx) -> usize {
+        self.count
+    }
+}
+
+impl<Ctx> MeasureWith<Ctx> for Sleb128 {
+    #[inline]
+    fn measure_with(&self, _ctx: &Ctx) -> usize {
+        self.count
+    }
+}
+
+impl TryIntoCtx for Uleb128 {
+    type Error = error::Error;
+    #[inline]
+    fn try_into_ctx(self, dst: &mut [u8], _ctx: ()) -> error::Result<usize> {
+        write_unsigned(dst, u128::from(self.value), self.count, 64)
+    }
+}
+
+impl TryIntoCtx for &Uleb128 {
+    type Error = error::Error;
+    #[inline]
+    fn try_into_ctx(self, dst: &mut [u8], ctx: ()) -> error::Result<usize> {
+        (*self).try_into_ctx(dst, ctx)
+    }
+}
+
+impl TryIntoCtx for Sleb128 {
+    type Error = error::Error;
+    #[inline]
+    fn try_into_ctx(self, dst: &mut [u8], _ctx: ()) -> error::Result<usize> {
+        write_signed(dst, i128::from(self.value), self.count, 64)
+    }
+}
+
+impl TryIntoCtx for &Sleb128 {
+    type Error = error::Error;
+    #[inline]
+    fn try_into_ctx(self, dst: &mut [u8], ctx: ()) -> error::Result<usize> {
+        (*self).try_into_ctx(dst, ctx)
+    }
+}
+
+/// How many bytes the canonical encoding of `value` takes up
+#[inline]
+pub(crate) fn unsigned_size(mut value: u128) -> usize {
+    let mut count = 1;
+    value >>= 7;
+    while value != 0 {
+        count += 1;
+        value >>= 7;
+    }
+    count
+}
+
+/// How many bytes the canonical encoding of `value` takes up
+#[inline]
+fn signed_size(mut value: i128) -> usize {
+    let mut count = 1;
+    loop {
+        let byte = value as u8;
+        value >>= 7;
+        if (value == 0 && byte & SIGN_BIT == 0) || (value == -1 && byte & SIGN_BIT != 0) {
+            return count;
+        }
+        count += 1;
+    }
+}
+
+/// Writes exactly `count` bytes, padding with continuation bytes if `count` is larger than the
+/// canonical size, so that padded encodings which were read in are written back out unchanged
+pub(crate) fn write_unsigned(
+    dst: &mut [u8],
+    mut value: u128,
+    count: usize,
+    bits: u32,
+) -> error::Result<usize> {
+    if bits < 128 && value >> bits != 0 {
+        return Err(error::Error::BadInput {
+            size: count,
+            msg: "value overflows the leb128 bit width",
+        });
+    }
+    if count > dst.len() {
+        return Err(error::Error::TooBig {
+            size: count,
+            len: dst.len(),
+        });
+    }
+    for (i, byte) in dst[..count].iter_mut().enumerate() {
+        *byte = mask_continuation(value as u8);
+        value >>= 7;
+        if i + 1 < count {
+            *byte |= CONTINUATION_BIT;
+        }
+    }
+    Ok(count)
+}
+
+/// The signed counterpart of `write_unsigned`; padding bytes carry the sign
+fn write_signed(dst: &mut [u8], mut value: i128, count: usize, bits: u32) -> error::Result<usize> {
+    if bits < 128 && (value >> (bits - 1) != 0 && value >> (bits - 1) != -1) {
+        return Err(error::Error::BadInput {
+            size: count,
+            msg: "value overflows the leb128 bit width",
+        });
+    }
+    if count > dst.len() {
+        return Err(error::Error::TooBig {
+            size: count,
+            len: dst.len(),
+        });
+    }
+    for (i, byte) in dst[..count].iter_mut().enumerate() {
+        *byte = mask_continuation(value as u8);
+        value >>= 7;
+        if i + 1 < count {
+            *byte |= CONTINUATION_BIT;
+        }
+    }
+    Ok(count)
+}
+
+/// The parsing context for the width-bounded leb128 types, e.g. [VarUint32](struct.VarUint32.html)
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum Leb128Ctx {
+    /// Accept padded encodings, as long as they fit in the maximum number of bytes for the width
+    #[default]
+    Lenient,
+    /// Reject non-canonical (padded) encodings, e.g. `[0x80, 0x00]` for 0, and write the canonical
+    /// encoding even for values which were read padded
+    Strict,
+}
+
+/// Reads an unsigned leb128 of at most `bits` significant bits, rejecting encodings which are
+/// longer than `ceil(bits / 7)` bytes or whose last byte has bits set past the width
+pub(crate) fn read_unsigned(src: &[u8], bits: u32, strict: bool) -> error::Result<(u128, usize)> {
+    let max = bits.div_ceil(7) as usize;
+    let mut result = 0;
+    let mut shift = 0;
+    let mut count = 0;
+    loop {
+        let byte: u8 = src.pread(count)?;
+        let low_bits = mask_continuation(byte);
+        count += 1;
+
+        if count == max {
+            let remaining = bits - shift;
+            if byte & CONTINUATION_BIT != 0 || (remaining < 7 && low_bits >> remaining != 0) {
+                return Err(error::Error::BadInput {
+                    size: src.len(),
+                    msg: "leb128 overflows its bit width",
+                });
+            }
+        }
+
+        result |= u128::from(low_bits) << shift;
+        shift += 7;
+
+        if byte & CONTINUATION_BIT == 0 {
+            if strict && count > 1 && byte == 0 {
+                return Err(error::Error::BadInput {
+                    size: src.len(),
+                    msg: "non-canonical leb128",
+                });
+            }
+            return Ok((result, count));
+        }
+    }
+}
+
+/// Reads a signed leb128 of at most `bits` significant bits; the unused bits of the last byte
+/// must be a sign extension of the value
+fn read_signed(src: &[u8], bits: u32, strict: bool) -> error::Result<(i128, usize)> {
+    let max = bits.div_ceil(7) as usize;
+    let mut result = 0;
+    let mut shift = 0;
+    let mut count = 0;
+    let mut byte: u8;
+    loop {
+        byte = src.pread(count)?;
+        let low_bits = mask_continuation(byte);
+        count += 1;
+
+        if count == max {
+            let unused = 8 - (bits - shift);
+            let extended = mask_continuation((((low_bits << unused) as i8) >> unused) as u8);
+            if byte & CONTINUATION_BIT != 0 || extended != low_bits {
+                return Err(error::Error::BadInput {
+                    size: src.len(),
+                    msg: "leb128 overflows its bit width",
+                });
+            }
+        }
+
+        result |= i128::from(low_bits) << shift;
+        shift += 7;
+
+        if byte & CONTINUATION_BIT == 0 {
+            break;
+        }
+    }
+
+    if strict && count > 1 {
+        let previous = src[count - 2];
+        if (byte == 0x00 && previous & SIGN_BIT == 0) || (byte == 0x7f && previous & SIGN_BIT != 0)
+        {
+            return Err(error::Error::BadInput {
+                size: src.len(),
+                msg: "non-canonical leb128",
+            });
+        }
+    }
+
+    if shift < 128 && (SIGN_BIT & byte) == SIGN_BIT {
+        // Sign extend the result.
+        result |= !0 << shift;
+    }
+    Ok((result, count))
+}
+
+macro_rules! leb128_width_impl {
+    ($name:ident, $typ:ty, $bits:expr, $wide:ty, $read:ident, $write:ident, $size:ident, $doc:expr) => {
+        #[derive(Debug, PartialEq, Eq, Copy, Clone)]
+        #[doc = $doc]
+        pub struct $name {
+            value: $typ,
+            count: usize,
+        }
+
+        impl $name {
+            /// The number of significant bits this leb128 may hold
+            pub const BITS: u32 = $bits;
+            /// The maximum number of bytes an encoding of this leb128 may take up
+            pub const MAX_SIZE: usize = ($bits as usize).div_ceil(7);
+
+            #[inline]
+            /// Return how many bytes this leb128 takes up in memory
+            pub fn size(&self) -> usize {
+                self.count
+            }
+            #[inline]
+            #[doc = concat!("Read a variable length `", stringify!($typ), "` from `bytes` at `offset`")]
+            pub fn read(bytes: &[u8], offset: &mut usize) -> error::Result<$typ> {
+                let tmp = bytes.pread::<$name>(*offset)?;
+                *offset += tmp.size();
+                Ok(tmp.into())
+            }
+            #[inline]
+            #[doc = concat!("Write `value` as a variable length `", stringify!($typ), "` into `bytes` at `offset`, returning the number of bytes written")]
+            pub fn write(bytes: &mut [u8], offset: &mut usize, value: $typ) -> error::Result<usize> {
+                bytes.gwrite($name::from(value), offset)
+            }
+        }
+
+        impl From<$typ> for $name {
+            #[inline]
+            fn from(value: $typ) -> $name {
+                $name {
+                    value,
+                    count: $size(<$wide>::from(value)),
+                }
+            }
+        }
+
+        impl AsRef<$typ> for $name {
+            fn as_ref(&self) -> &$typ {
+                &self.value
+            }
+        }
+
+        impl From<$name> for $typ {
+            #[inline]
+            fn from(leb128: $name) -> $typ {
+                leb128.value
+            }
+        }
+
+        /// The number of bytes `ctx` writes: the size it was read with, or the canonical size under
+        /// `Leb128Ctx::Strict`
+        impl MeasureWith<Leb128Ctx> for $name {
+            #[inline]
+            fn measure_with(&self, ctx: &Leb128Ctx) -> usize {
+                match ctx {
+                    Leb128Ctx::Lenient => self.count,
+                    Leb128Ctx::Strict => $size(<$wide>::from(self.value)),
+                }
+            }
+        }
+
+        impl<'a> TryFromCtx<'a, Leb128Ctx> for $name {
+            type Error = error::Error;
+            #[inline]
+            fn try_from_ctx(
+                src: &'a [u8],
+                ctx: Leb128Ctx,
+            ) -> result::Result<(Self, usize), Self::Error> {
+                let (value, count) = $read(src, $bits, ctx == Leb128Ctx::Strict)?;
+                Ok((
+                    $name {
+                        value: value as $typ,
+                        count,
+                    },
+                    count,
+                ))
+            }
+        }
+
+        impl TryIntoCtx<Leb128Ctx> for $name {
+            type Error = error::Error;
+            #[inline]
+            fn try_into_ctx(self, dst: &mut [u8], ctx: Leb128Ctx) -> error::Result<usize> {
+                let count = self.measure_with(&ctx);
+                $write(dst, <$wide>::from(self.value), count, $bits)
+            }
+        }
+
+        impl TryIntoCtx<Leb128Ctx> for &$name {
+            type Error = error::Error;
+            #[inline]
+            fn try_into_ctx(self, dst: &mut [u8], ctx: Leb128Ctx) -> error::Result<usize> {
+                (*self).try_into_ctx(dst, ctx)
+            }
+        }
+    };
+}
+
+macro_rules! uleb128_width_impl {
+    ($name:ident, $typ:ty, $bits:expr) => {
+        leb128_width_impl!(
+            $name,
+            $typ,
+            $bits,
+            u128,
+            read_unsigned,
+            write_unsigned,
+            unsigned_size,
+            concat!("A ", $bits, "-bit unsigned leb128 integer")
+        );
+    };
+}
+
+macro_rules! sleb128_width_impl {
+    ($name:ident, $typ:ty, $bits:expr) => {
+        leb128_width_impl!(
+            $name,
+            $typ,
+            $bits,
+            i128,
+            read_signed,
+            write_signed,
+            signed_size,
+            concat!("A ", $bits, "-bit signed leb128 integer")
+        );
+    };
+}
+
+uleb128_width_impl!(VarUint1, u8, 1);
+uleb128_width_impl!(VarUint7, u8, 7);
+uleb128_width_impl!(VarUint32, u32, 32);
+uleb128_width_impl!(VarUint64, u64, 64);
+uleb128_width_impl!(VarUint128, u128, 128);
+sleb128_width_impl!(VarInt7, i8, 7);
+sleb128_width_impl!(VarInt32, i32, 32);
+sleb128_width_impl!(VarInt64, i64, 64);
+sleb128_width_impl!(VarInt128, i128, 128);
+
 #[cfg(test)]
 mod tests {
     use super::super::LE;
-    use super::{Sleb128, Uleb128};
+    use super::{
+        Leb128Ctx, Sleb128, Uleb128, VarInt7, VarInt32, VarInt64, VarInt128, VarUint1, VarUint7,
+        VarUint32, VarUint128,
+    };
 
     const CONTINUATION_BIT: u8 = 1 << 7;
     //const SIGN_BIT: u8 = 1 << 6;
@@ -248,4 +651,197 @@ mod tests {
             .into();
         assert_eq!(-129, num);
     }
+
+    #[test]
+    fn uleb128_roundtrip() {
+        use super::super::{Pread, Pwrite};
+        for value in [0u64, 1, 0x7f, 0x80, 624485, u32::MAX as u64, u64::MAX] {
+            let uleb = Uleb128::from(value);
+            let mut buf = [0u8; 16];
+            let offset = &mut 0;
+            assert_eq!(buf.gwrite(uleb, offset).unwrap(), uleb.size());
+            assert_eq!(*offset, uleb.size());
+            let read = buf.pread::<Uleb128>(0).unwrap();
+            assert_eq!(read, uleb);
+            assert_eq!(Uleb128::read(&buf, &mut 0).unwrap(), value);
+        }
+        assert_eq!(Uleb128::from(624485).size(), 3);
+        assert_eq!(Uleb128::from(u64::MAX).size(), 10);
+        let mut buf = [0u8; 3];
+        buf.pwrite(Uleb128::from(624485), 0).unwrap();
+        assert_eq!(buf, [0xe5, 0x8e, 0x26]);
+    }
+
+    #[test]
+    fn sleb128_roundtrip() {
+        use super::super::{Pread, Pwrite};
+        for value in [
+            0i64,
+            1,
+            -1,
+            63,
+            64,
+            -64,
+            -65,
+            -129,
+            -123456,
+            i64::MIN,
+            i64::MAX,
+        ] {
+            let sleb = Sleb128::from(value);
+            let mut buf = [0u8; 16];
+            let offset = &mut 0;
+            assert_eq!(buf.gwrite(sleb, offset).unwrap(), sleb.size());
+            let read = buf.pread::<Sleb128>(0).unwrap();
+            assert_eq!(read, sleb);
+            assert_eq!(Sleb128::read(&buf, &mut 0).unwrap(), value);
+        }
+        assert_eq!(Sleb128::from(63).size(), 1);
+        assert_eq!(Sleb128::from(64).size(), 2);
+        assert_eq!(Sleb128::from(i64::MIN).size(), 10);
+        let mut buf = [0u8; 3];
+        buf.pwrite(Sleb128::from(-123456), 0).unwrap();
+        assert_eq!(buf, [0xc0, 0xbb, 0x78]);
+    }
+
+    #[test]
+    fn leb128_padded_roundtrip() {
+        use super::super::{Pread, Pwrite};
+        let padded = [0x80u8, 0x80, 0x00];
+        let uleb = padded.pread::<Uleb128>(0).unwrap();
+        assert_eq!(uleb.size(), 3);
+        let mut buf = [0xffu8; 3];
+        buf.pwrite(uleb, 0).unwrap();
+        assert_eq!(buf, padded);
+
+        let padded = [0xffu8, 0xff, 0x7f];
+        let sleb = padded.pread::<Sleb128>(0).unwrap();
+        assert_eq!(i64::from(sleb), -1);
+        let mut buf = [0u8; 3];
+        buf.pwrite(sleb, 0).unwrap();
+        assert_eq!(buf, padded);
+    }
+
+    #[test]
+    fn leb128_write_too_big() {
+        use super::super::Pwrite;
+        use super::super::ctx::MeasureWith;
+        let uleb = Uleb128::from(u64::MAX);
+        assert_eq!(uleb.measure_with(&()), 10);
+        let mut buf = [0u8; 4];
+        assert!(buf.pwrite(uleb, 0).is_err());
+        assert!(buf.pwrite(Sleb128::from(i64::MIN), 0).is_err());
+        let offset = &mut 2;
+        assert_eq!(Uleb128::write(&mut buf, offset, 300).unwrap(), 2);
+        assert_eq!(*offset, 4);
+        assert_eq!(buf[2..], [0xac, 0x02]);
+    }
+
+    #[test]
+    fn width_bounded_roundtrip() {
+        use super::super::{Pread, Pwrite};
+        macro_rules! roundtrip {
+            ($name:ident, $($value:expr),*) => {
+                $({
+                    let leb = $name::from($value);
+                    assert!(leb.size() <= $name::MAX_SIZE);
+                    let mut buf = [0u8; 20];
+                    assert_eq!(buf.pwrite(leb, 0).unwrap(), leb.size());
+                    let read = buf.pread_with::<$name>(0, Leb128Ctx::Strict).unwrap();
+                    assert_eq!(read, leb);
+                    assert_eq!($name::read(&buf, &mut 0).unwrap(), $value);
+                })*
+            };
+        }
+        roundtrip!(VarUint1, 0, 1);
+        roundtrip!(VarUint7, 0, 0x7f);
+        roundtrip!(VarUint32, 0, 0x80, u32::MAX);
+        roundtrip!(VarUint128, 0, u64::MAX as u128 + 1, u128::MAX);
+        roundtrip!(VarInt7, -64, -1, 0, 63);
+        roundtrip!(VarInt32, i32::MIN, -1, 0, i32::MAX);
+        roundtrip!(VarInt64, i64::MIN, i64::MAX);
+        roundtrip!(VarInt128, i128::MIN, -1, 0, i128::MAX);
+        assert_eq!(VarUint32::MAX_SIZE, 5);
+        assert_eq!(VarUint128::from(u128::MAX).size(), 19);
+        assert_eq!(VarInt128::from(i128::MIN).size(), 19);
+    }
+
+    #[test]
+    fn width_bounded_overflow() {
+        use super::super::Pread;
+        // 2^32 does not fit
+        let bytes = [0x80u8, 0x80, 0x80, 0x80, 0x10];
+        assert!(bytes.pread::<VarUint32>(0).is_err());
+        // u32::MAX does
+        let bytes = [0xffu8, 0xff, 0xff, 0xff, 0x0f];
+        assert_eq!(bytes.pread::<VarUint32>(0).unwrap().size(), 5);
+        // too many bytes, even though the value is 0
+        let bytes = [0x80u8, 0x80, 0x80, 0x80, 0x80, 0x00];
+        assert!(bytes.pread::<VarUint32>(0).is_err());
+        assert!([0x02u8].pread::<VarUint1>(0).is_err());
+        assert!([0x81u8, 0x00].pread::<VarUint7>(0).is_err());
+        // i32::MIN and one past it
+        let bytes = [0x80u8, 0x80, 0x80, 0x80, 0x78];
+        assert_eq!(i32::from(bytes.pread::<VarInt32>(0).unwrap()), i32::MIN);
+        let bytes = [0xffu8, 0xff, 0xff, 0xff, 0x77];
+        assert!(bytes.pread::<VarInt32>(0).is_err());
+        // positive value with bits past the sign
+        let bytes = [0xffu8, 0xff, 0xff, 0xff, 0x08];
+        assert!(bytes.pread::<VarInt32>(0).is_err());
+    }
+
+    #[test]
+    fn width_bounded_strict() {
+        use super::super::{Pread, Pwrite};
+        use crate::ctx::MeasureWith;
+        let padded = [0x80u8, 0x00];
+        let lenient = padded.pread::<VarUint32>(0).unwrap();
+        assert_eq!(u32::from(lenient), 0);
+        assert_eq!(lenient.size(), 2);
+        assert!(
+            padded
+                .pread_with::<VarUint32>(0, Leb128Ctx::Strict)
+                .is_err()
+        );
+        let mut buf = [0xffu8; 2];
+        buf.pwrite(lenient, 0).unwrap();
+        assert_eq!(buf, padded);
+        assert_eq!(lenient.measure_with(&Leb128Ctx::Lenient), 2);
+        assert_eq!(lenient.measure_with(&Leb128Ctx::Strict), 1);
+        let mut buf = [0xffu8; 2];
+        assert_eq!(buf.pwrite_with(lenient, 0, Leb128Ctx::Strict).unwrap(), 1);
+        assert_eq!(buf, [0x00, 0xff]);
+
+        // -1 padded with the sign
+        let padded = [0xffu8, 0x7f];
+        assert_eq!(i32::from(padded.pread::<VarInt32>(0).unwrap()), -1);
+        assert!(padded.pread_with::<VarInt32>(0, Leb128Ctx::Strict).is_err());
+        let lenient = padded.pread::<VarInt32>(0).unwrap();
+        assert_eq!(lenient.measure_with(&Leb128Ctx::Strict), 1);
+        let mut buf = [0u8; 2];
+        assert_eq!(buf.pwrite_with(&lenient, 0, Leb128Ctx::Strict).unwrap(), 1);
+        assert_eq!(buf, [0x7f, 0x00]);
+        // 63 padded
+        assert!(
+            [0xbfu8, 0x00]
+                .pread_with::<VarInt32>(0, Leb128Ctx::Strict)
+                .is_err()
+        );
+        // but 64 and -65 need the second byte
+        let strict = [0xc0u8, 0x00].pread_with::<VarInt32>(0, Leb128Ctx::Strict);
+        assert_eq!(i32::from(strict.unwrap()), 64);
+        let strict = [0xbfu8, 0x7f].pread_with::<VarInt32>(0, Leb128Ctx::Strict);
+        assert_eq!(i32::from(strict.unwrap()), -65);
+    }
+
+    #[test]
+    fn width_bounded_write_overflow() {
+        use super::super::Pwrite;
+        let mut buf = [0u8; 4];
+        assert!(buf.pwrite(VarUint1::from(2), 0).is_err());
+        assert!(buf.pwrite(VarUint7::from(0x80), 0).is_err());
+        assert!(buf.pwrite(VarInt7::from(64), 0).is_err());
+        assert!(buf.pwrite(VarInt7::from(-65), 0).is_err());
+        assert!(buf.pwrite(VarUint32::from(u32::MAX), 0).is_err());
+    }
 }
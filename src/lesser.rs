@@ -1,18 +1,85 @@
-use std::io::{Read, Result, Write};
+use std::io::{self, BufRead, Read, Result, Write};
 
-use crate::ctx::{FromCtx, IntoCtx, SizeWith};
+use crate::ctx::{FromCtx, IntoCtx, SizeWith, TryFromCtx, TryIntoCtx};
+use crate::error::{self, Error};
+use crate::writer::VecWriter;
+
+/// The size of the buffer on the stack values are read into and written from
+pub(crate) const SCRATCH_SIZE: usize = 256;
+
+/// A zeroed buffer of `size` bytes on the heap, for values which don't fit in the scratch buffer;
+/// failing to allocate it is an `io::ErrorKind::OutOfMemory` error.
+pub(crate) fn heap_scratch(size: usize) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    buf.try_reserve_exact(size)
+        .map_err(|err| io::Error::new(io::ErrorKind::OutOfMemory, err))?;
+    buf.resize(size, 0);
+    Ok(buf)
+}
+
+/// Calls `f` with a zeroed buffer of `size` bytes, which is on the stack unless `size` is large
+fn with_scratch<T>(size: usize, f: impl FnOnce(&mut [u8]) -> Result<T>) -> Result<T> {
+    let mut scratch = [0u8; SCRATCH_SIZE];
+    match scratch.get_mut(..size) {
+        Some(buf) => f(buf),
+        None => f(&mut heap_scratch(size)?),
+    }
+}
+
+/// Parses a value from the successive buffers of a buffered stream, for `BufIOread` and its
+/// async counterparts
+#[derive(Default)]
+pub(crate) struct Incremental {
+    // the bytes consumed so far, once the value spans more than one buffer
+    prefix: Vec<u8>,
+}
+
+impl Incremental {
+    /// Parses the value with the next buffer of the stream, which is empty at its end. Returns the
+    /// value, if it is complete, and the number of bytes to consume from the buffer.
+    pub(crate) fn feed<N: for<'a> TryFromCtx<'a, Ctx, Error = Error>, Ctx: Copy>(
+        &mut self,
+        available: &[u8],
+        ctx: Ctx,
+    ) -> error::Result<(Option<N>, usize)> {
+        let start = self.prefix.len();
+        let parsed = if start == 0 {
+            N::try_from_ctx(available, ctx)
+        } else {
+            self.prefix.extend_from_slice(available);
+            N::try_from_ctx(&self.prefix, ctx)
+        };
+        match parsed {
+            // parsing the first `start` bytes failed, so the value ends in this buffer
+            Ok((n, size)) => Ok((Some(n), size.saturating_sub(start))),
+            Err(err)
+                if !available.is_empty()
+                    && matches!(err.root_cause(), Error::TooBig { .. } | Error::BadOffset(_)) =>
+            {
+                if start == 0 {
+                    self.prefix.extend_from_slice(available);
+                }
+                Ok((None, available.len()))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
 
 /// An extension trait to `std::io::Read` streams; mainly targeted at reading primitive types with
 /// a known size.
 ///
 /// Requires types to implement [`FromCtx`](ctx/trait.FromCtx.html) and [`SizeWith`](ctx/trait.SizeWith.html).
+/// To read types which only implement [`TryFromCtx`](ctx/trait.TryFromCtx.html), see
+/// [`BufIOread`](trait.BufIOread.html).
 ///
 /// **NB** You should probably add `repr(C)` and be very careful how you implement
 /// [`SizeWith`](ctx/trait.SizeWith.html), otherwise you will get IO errors failing to fill entire
 /// buffer (the size you specified in `SizeWith`), or out of bound errors (depending on your impl)
 /// in `from_ctx`.
 ///
-/// Warning: Currently ioread/write uses a small 256-byte buffer and can not read/write larger types
+/// Types of up to 256 bytes are read through a buffer on the stack, larger ones through one on
+/// the heap.
 ///
 /// # Example
 /// ```rust
@@ -82,7 +149,6 @@ pub trait IOread<Ctx: Copy>: Read {
     }
 
     /// Reads the type `N` from `Self`, with the parsing context `ctx`.
-    /// **NB**: this will panic if the type you're reading has a size greater than 256. Plans are to have this allocate in larger cases.
     ///
     /// For the primitive numeric types, this will be at the host machine's endianness.
     ///
@@ -103,11 +169,10 @@ pub trait IOread<Ctx: Copy>: Read {
     /// ```
     #[inline]
     fn ioread_with<N: FromCtx<Ctx> + SizeWith<Ctx>>(&mut self, ctx: Ctx) -> Result<N> {
-        let mut scratch = [0u8; 256];
-        let size = N::size_with(&ctx);
-        let buf = &mut scratch[0..size];
-        self.read_exact(buf)?;
-        Ok(N::from_ctx(buf, ctx))
+        with_scratch(N::size_with(&ctx), |buf| {
+            self.read_exact(buf)?;
+            Ok(N::from_ctx(buf, ctx))
+        })
     }
 }
 
@@ -115,12 +180,74 @@ pub trait IOread<Ctx: Copy>: Read {
 /// for free.
 impl<Ctx: Copy, R: Read + ?Sized> IOread<Ctx> for R {}
 
+/// An extension trait to `std::io::BufRead` streams, reading types which only implement
+/// [`TryFromCtx`](ctx/trait.TryFromCtx.html), and whose size isn't known before reading them, like
+/// a [`Uleb128`](struct.Uleb128.html) or a derived struct with a `Vec` field.
+///
+/// The value is parsed from the bytes the stream has buffered. When they don't suffice, i.e.
+/// parsing fails with `Error::TooBig` or `Error::BadOffset`, more are read and parsing starts over;
+/// at the end of the stream that error is returned. Only the bytes of the value are consumed, so
+/// the stream is left right after it.
+///
+/// As each refill parses the value from its start again, a value of `size` bytes read through a
+/// buffer of `capacity` bytes takes `O(size² / capacity)`; give the stream a buffer at least as
+/// large as the values it usually reads. And a value failing with `Error::TooBig` or
+/// `Error::BadOffset` for another reason than the end of the buffered bytes, e.g. a corrupt
+/// length, is only reported at the end of the stream, after buffering all of it; bound untrusted
+/// streams with `Read::take`.
+///
+/// As the value can't borrow from the stream, `N` must be an owned type; it must also report its
+/// errors as a `scroll::Error`, which wraps any IO error.
+///
+/// # Example
+/// ```rust
+/// use scroll::{BufIOread, Uleb128, LE};
+/// use std::io::{BufReader, Read};
+/// let bytes = [0xe5, 0x8e, 0x26, 0xef, 0xbe, 0x01];
+/// // a tiny buffer, so that the values straddle refills
+/// let mut reader = BufReader::with_capacity(2, &bytes[..]);
+/// let n: Uleb128 = reader.try_ioread().unwrap();
+/// assert_eq!(u64::from(n), 624485);
+/// assert_eq!(reader.try_ioread_with::<u16>(LE).unwrap(), 0xbeef);
+/// let mut rest = Vec::new();
+/// reader.read_to_end(&mut rest).unwrap();
+/// assert_eq!(rest, [0x01]);
+/// ```
+pub trait BufIOread<Ctx: Copy>: BufRead {
+    /// Reads the type `N` from `Self`, with a default parsing context.
+    #[inline]
+    fn try_ioread<N: for<'a> TryFromCtx<'a, Ctx, Error = Error>>(&mut self) -> error::Result<N>
+    where
+        Ctx: Default,
+    {
+        self.try_ioread_with(Ctx::default())
+    }
+
+    /// Reads the type `N` from `Self`, with the parsing context `ctx`.
+    fn try_ioread_with<N: for<'a> TryFromCtx<'a, Ctx, Error = Error>>(
+        &mut self,
+        ctx: Ctx,
+    ) -> error::Result<N> {
+        let mut parser = Incremental::default();
+        loop {
+            let (n, consumed) = parser.feed(self.fill_buf()?, ctx)?;
+            self.consume(consumed);
+            if let Some(n) = n {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+/// Types that implement `BufRead` get methods defined in `BufIOread`
+/// for free.
+impl<Ctx: Copy, R: BufRead + ?Sized> BufIOread<Ctx> for R {}
+
 /// An extension trait to `std::io::Write` streams; this only serializes simple types, like `u8`, `i32`, `f32`, `usize`, etc.
 ///
 /// To write custom types with a single `iowrite::<YourType>` call, implement [`IntoCtx`](ctx/trait.IntoCtx.html) and [`SizeWith`](ctx/trait.SizeWith.html) for `YourType`.
 pub trait IOwrite<Ctx: Copy>: Write {
     /// Writes the type `N` into `Self`, with the parsing context `ctx`.
-    /// **NB**: this will panic if the type you're writing has a size greater than 256. Plans are to have this allocate in larger cases.
     ///
     /// For the primitive numeric types, this will be at the host machine's endianness.
     ///
@@ -148,7 +275,8 @@ pub trait IOwrite<Ctx: Copy>: Write {
     }
 
     /// Writes the type `N` into `Self`, with the parsing context `ctx`.
-    /// **NB**: this will panic if the type you're writing has a size greater than 256. Plans are to have this allocate in larger cases.
+    /// Types of up to 256 bytes are serialized into a buffer on the stack, larger ones into one on
+    /// the heap.
     ///
     /// For the primitive numeric types, this will be at the host machine's endianness.
     ///
@@ -165,12 +293,53 @@ pub trait IOwrite<Ctx: Copy>: Write {
     /// ```
     #[inline]
     fn iowrite_with<N: SizeWith<Ctx> + IntoCtx<Ctx>>(&mut self, n: N, ctx: Ctx) -> Result<()> {
-        let mut buf = [0u8; 256];
-        let size = N::size_with(&ctx);
-        let buf = &mut buf[0..size];
-        n.into_ctx(buf, ctx);
-        self.write_all(buf)?;
-        Ok(())
+        with_scratch(N::size_with(&ctx), |buf| {
+            n.into_ctx(buf, ctx);
+            self.write_all(buf)
+        })
+    }
+
+    /// Writes the type `N` into `Self`, with a default parsing context, and returns the number of
+    /// bytes written.
+    #[inline]
+    fn try_iowrite<N: TryIntoCtx<Ctx, Error = Error> + Clone>(
+        &mut self,
+        n: N,
+    ) -> error::Result<usize>
+    where
+        Ctx: Default,
+    {
+        self.try_iowrite_with(n, Ctx::default())
+    }
+
+    /// Writes the type `N` into `Self`, with the parsing context `ctx`, and returns the number of
+    /// bytes written.
+    ///
+    /// `N` only needs to implement [`TryIntoCtx`](ctx/trait.TryIntoCtx.html), and can be of any
+    /// size: it is serialized with a [`VecWriter`](struct.VecWriter.html) first, which is why it
+    /// must be `Clone`. Write a reference for types which aren't.
+    ///
+    /// # Example
+    /// ```rust
+    /// use scroll::{IOwrite, Uleb128, BE};
+    ///
+    /// let mut bytes = Vec::new();
+    /// assert_eq!(bytes.try_iowrite(Uleb128::from(624485)).unwrap(), 3);
+    /// bytes.try_iowrite_with("hello", ()).unwrap();
+    /// bytes.try_iowrite_with(&[0xab; 300][..], ()).unwrap();
+    /// bytes.try_iowrite_with(0xbeefu16, BE).unwrap();
+    /// assert_eq!(&bytes[..8], [0xe5, 0x8e, 0x26, b'h', b'e', b'l', b'l', b'o']);
+    /// assert_eq!(bytes.len(), 310);
+    /// ```
+    fn try_iowrite_with<N: TryIntoCtx<Ctx, Error = Error> + Clone>(
+        &mut self,
+        n: N,
+        ctx: Ctx,
+    ) -> error::Result<usize> {
+        let mut writer = VecWriter::new();
+        let size = writer.pwrite_with(n, 0, ctx)?;
+        self.write_all(writer.as_slice())?;
+        Ok(size)
     }
 }
 
@@ -222,6 +222,14 @@
 //!
 //! For further explanation of the traits and how to implement them manually refer to
 //! [Pread](trait.Pread.html) and [TryFromCtx](ctx/trait.TryFromCtx.html).
+//!
+//! ## `no_std`
+//!
+//! Scroll is `no_std` when the default `std` feature is disabled. The `alloc` feature brings back
+//! the heap-backed parts — reading `Vec<T>`, `Box<[T]>` and `CString`, the growing
+//! [VecWriter](struct.VecWriter.html) and
+//! [`Error::Custom`](enum.Error.html#variant.Custom) — without requiring `std`; the `std::io`
+//! based [IOread](trait.IOread.html) and [IOwrite](trait.IOwrite.html) still need `std`.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
@@ -232,16 +240,36 @@ pub use scroll_derive::{IOread, IOwrite, Pread, Pwrite, SizeWith};
 #[cfg(feature = "std")]
 extern crate core;
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+#[macro_use]
+mod async_io;
+mod bits;
 pub mod ctx;
+mod cursor;
 mod endian;
 mod error;
+#[cfg(feature = "futures-io")]
+pub mod futures_io;
 mod greater;
 mod leb128;
 #[cfg(feature = "std")]
 mod lesser;
 mod pread;
 mod pwrite;
-
+#[cfg(feature = "std")]
+mod source;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+mod varint;
+mod window;
+#[cfg(feature = "alloc")]
+mod writer;
+
+pub use crate::bits::*;
+pub use crate::cursor::*;
 pub use crate::endian::*;
 pub use crate::error::*;
 pub use crate::greater::*;
@@ -250,10 +278,18 @@ pub use crate::leb128::*;
 pub use crate::lesser::*;
 pub use crate::pread::*;
 pub use crate::pwrite::*;
+#[cfg(feature = "std")]
+pub use crate::source::*;
+pub use crate::varint::*;
+pub use crate::window::*;
+#[cfg(feature = "alloc")]
+pub use crate::writer::*;
 
 #[doc(hidden)]
 pub mod export {
     pub use ::core::{mem, result};
+    #[cfg(feature = "alloc")]
+    pub use alloc::vec;
 }
 
 #[allow(unused)]
@@ -449,6 +485,7 @@ mod tests {
     }
 
     impl From<super::Error> for ExternalError {
+        #[allow(clippy::match_single_binding)]
         fn from(err: super::Error) -> Self {
             //use super::Error::*;
             match err {
@@ -486,6 +523,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_range_loop)]
     fn pread_with_iter_bytes() {
         use super::Pread;
         let mut bytes_to: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
@@ -580,6 +618,7 @@ mod tests {
 
     // useful for ferreting out problems with impls
     #[test]
+    #[allow(clippy::needless_range_loop, clippy::needless_borrow)]
     fn gread_with_iter_bytes() {
         use super::Pread;
         let mut bytes_to: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
@@ -606,6 +645,46 @@ mod tests {
         assert_eq!(*offset, bytes_to.len());
     }
 
+    #[test]
+    fn pread_iter_variable_sized() {
+        use super::Pread;
+        use super::ctx::StrCtx;
+        let bytes = b"hello\0scroll\0world\0";
+        let mut iter = bytes.pread_iter_with::<&str>(0, None, StrCtx::Delimiter(0));
+        assert_eq!(iter.next().unwrap().unwrap(), "hello");
+        assert_eq!(iter.offset(), 6);
+        assert_eq!(iter.next().unwrap().unwrap(), "scroll");
+        assert_eq!(iter.next().unwrap().unwrap(), "world");
+        assert_eq!(iter.offset(), bytes.len());
+        assert!(iter.next().is_none());
+
+        let mut iter = bytes.pread_iter_with::<&str>(6, Some(1), StrCtx::Delimiter(0));
+        assert_eq!(iter.size_hint(), (0, Some(1)));
+        assert_eq!(iter.next().unwrap().unwrap(), "scroll");
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn pread_iter_stops_at_first_error() {
+        use super::{Error, Pread};
+        let bytes: [u8; 5] = [0, 1, 0, 2, 0];
+        let mut iter = bytes.pread_iter_with::<u16>(0, Some(4), LE);
+        assert_eq!(iter.next().unwrap().unwrap(), 0x100);
+        assert_eq!(iter.next().unwrap().unwrap(), 0x200);
+        assert!(matches!(iter.next(), Some(Err(Error::TooBig { .. }))));
+        assert!(iter.next().is_none());
+        assert_eq!(iter.offset(), 4);
+
+        // a trailing partial record is an error when reading until the end
+        let mut iter = bytes.pread_iter_with::<u16>(0, None, LE);
+        assert!(iter.nth(2).unwrap().is_err());
+
+        // as is an item which doesn't consume anything
+        let mut iter = bytes.pread_iter_with::<&[u8]>(0, None, 0);
+        assert!(matches!(iter.next(), Some(Err(Error::BadInput { .. }))));
+        assert!(iter.next().is_none());
+    }
+
     #[test]
     fn gread_with_byte() {
         use super::Pread;
@@ -1,6 +1,8 @@
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
 use core::result;
 
-use crate::ctx::TryFromCtx;
+use crate::ctx::{MeasureWith, TryFromCtx};
 use crate::error;
 
 /// A very generic, contextual pread interface in Rust.
@@ -163,6 +165,141 @@ pub trait Pread<Ctx: Copy, E> {
         }
         Ok(())
     }
+
+    /// Lazily reads `N`s from `self` starting at `offset` with a default `Ctx`; see
+    /// [pread_iter_with](trait.Pread.html#method.pread_iter_with).
+    /// # Example
+    /// ```rust
+    /// use scroll::Pread;
+    /// let bytes: [u8; 3] = [0x1, 0x2, 0x3];
+    /// let sum: u8 = bytes.pread_iter::<u8>(1, None).map(|n| n.unwrap()).sum();
+    /// assert_eq!(sum, 5);
+    /// ```
+    #[inline]
+    fn pread_iter<'a, N: TryFromCtx<'a, Ctx, Self, Error = E>>(
+        &'a self,
+        offset: usize,
+        count: Option<usize>,
+    ) -> PreadIter<'a, N, Ctx, E, Self>
+    where
+        Ctx: Default,
+    {
+        self.pread_iter_with(offset, count, Ctx::default())
+    }
+
+    /// Lazily reads `N`s from `self` starting at `offset` with the given `ctx`, without
+    /// collecting them.
+    ///
+    /// With `Some(count)` exactly `count` items are read; with `None` items are read until the end
+    /// of `self`. Each item advances the offset by the size its `TryFromCtx` implementation
+    /// reports, so variable sized records work too. The iterator stops after the first error.
+    /// # Example
+    /// ```rust
+    /// use scroll::{Pread, BE};
+    /// let bytes: [u8; 6] = [0xde, 0xad, 0xbe, 0xef, 0xca, 0xfe];
+    /// let mut iter = bytes.pread_iter_with::<u16>(0, Some(2), BE);
+    /// assert_eq!(iter.next().unwrap().unwrap(), 0xdead);
+    /// assert_eq!(iter.offset(), 2);
+    /// assert_eq!(iter.next().unwrap().unwrap(), 0xbeef);
+    /// assert!(iter.next().is_none());
+    ///
+    /// let all: Result<Vec<u16>, scroll::Error> = bytes.pread_iter_with(0, None, BE).collect();
+    /// assert_eq!(all.unwrap(), [0xdead, 0xbeef, 0xcafe]);
+    /// ```
+    #[inline]
+    fn pread_iter_with<'a, N: TryFromCtx<'a, Ctx, Self, Error = E>>(
+        &'a self,
+        offset: usize,
+        count: Option<usize>,
+        ctx: Ctx,
+    ) -> PreadIter<'a, N, Ctx, E, Self> {
+        PreadIter {
+            data: self,
+            offset,
+            count,
+            ctx,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// A lazy iterator reading consecutive `N`s out of a data store, created by
+/// [pread_iter_with](trait.Pread.html#method.pread_iter_with).
+pub struct PreadIter<'a, N, Ctx: Copy, E, Data: ?Sized = [u8]> {
+    data: &'a Data,
+    offset: usize,
+    count: Option<usize>,
+    ctx: Ctx,
+    done: bool,
+    _marker: PhantomData<fn() -> result::Result<N, E>>,
+}
+
+impl<N, Ctx: Copy, E, Data: ?Sized> PreadIter<'_, N, Ctx, E, Data> {
+    /// The offset the next item will be read from
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a, N, Ctx, E, Data> Iterator for PreadIter<'a, N, Ctx, E, Data>
+where
+    N: TryFromCtx<'a, Ctx, Data, Error = E>,
+    Ctx: Copy,
+    E: From<error::Error>,
+    Data: Pread<Ctx, E> + MeasureWith<Ctx> + ?Sized,
+{
+    type Item = result::Result<N, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.count {
+            Some(0) => {
+                self.done = true;
+                return None;
+            }
+            Some(ref mut count) => *count -= 1,
+            None if self.offset >= self.data.measure_with(&self.ctx) => {
+                self.done = true;
+                return None;
+            }
+            None => (),
+        }
+        let start = self.offset;
+        let res = self.data.gread_with(&mut self.offset, self.ctx);
+        if res.is_err() {
+            self.done = true;
+        } else if self.count.is_none() && self.offset == start {
+            // nothing was consumed, so reading until the end would never terminate
+            self.done = true;
+            return Some(Err(error::Error::BadInput {
+                size: 0,
+                msg: "zero sized item while reading until the end",
+            }
+            .into()));
+        }
+        Some(res)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.count {
+            _ if self.done => (0, Some(0)),
+            Some(count) => (0, Some(count)),
+            None => (0, None),
+        }
+    }
+}
+
+impl<'a, N, Ctx, E, Data> FusedIterator for PreadIter<'a, N, Ctx, E, Data>
+where
+    N: TryFromCtx<'a, Ctx, Data, Error = E>,
+    Ctx: Copy,
+    E: From<error::Error>,
+    Data: Pread<Ctx, E> + MeasureWith<Ctx> + ?Sized,
+{
 }
 
 impl<Ctx: Copy, E: From<error::Error>> Pread<Ctx, E> for [u8] {
@@ -175,9 +312,12 @@ impl<Ctx: Copy, E: From<error::Error>> Pread<Ctx, E> for [u8] {
         if start > self.len() {
             return Err(error::Error::BadOffset(start).into());
         }
-        N::try_from_ctx(&self[start..], ctx).map(|(n, size)| {
-            *offset += size;
-            n
-        })
+        match N::try_from_ctx(&self[start..], ctx) {
+            Ok((n, size)) => {
+                *offset += size;
+                Ok(n)
+            }
+            Err(err) => Err(N::offset_error(err, start)),
+        }
     }
 }
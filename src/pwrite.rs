@@ -80,9 +80,8 @@ pub trait Pwrite<Ctx: Copy, E> {
         ctx: Ctx,
     ) -> result::Result<usize, E> {
         let o = *offset;
-        self.pwrite_with(n, o, ctx).map(|size| {
+        self.pwrite_with(n, o, ctx).inspect(|size| {
             *offset += size;
-            size
         })
     }
 }
@@ -99,5 +98,6 @@ impl<Ctx: Copy, E: From<error::Error>> Pwrite<Ctx, E> for [u8] {
         }
         let dst = &mut self[offset..];
         n.try_into_ctx(dst, ctx)
+            .map_err(|err| N::offset_error(err, offset))
     }
 }
@@ -0,0 +1,509 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::ctx::TryFromCtx;
+use crate::error::{self, Error};
+use crate::lesser::heap_scratch;
+
+/// The number of bytes first read for a value of unknown size
+const INITIAL_READ: usize = 64;
+
+/// A source of bytes which can be read at any offset, like a file.
+///
+/// Implemented for `File` with positional reads (`pread` on unix, `seek_read` on windows), for
+/// any `Read + Seek` stream through [SeekReader](struct.SeekReader.html), and for the
+/// [PageCache](struct.PageCache.html) in front of either. Reading values out of it is done with
+/// [PreadAt](trait.PreadAt.html).
+pub trait ReadAt {
+    /// Reads bytes at `offset` into `buf`, returning how many were read; fewer bytes than `buf`
+    /// can hold may be read, and none at the end of the source.
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+}
+
+impl<S: ReadAt + ?Sized> ReadAt for &mut S {
+    #[inline]
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+}
+
+#[cfg(unix)]
+impl ReadAt for &File {
+    #[inline]
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::read_at(*self, buf, offset)
+    }
+}
+
+#[cfg(windows)]
+impl ReadAt for &File {
+    #[inline]
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(*self, buf, offset)
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl ReadAt for File {
+    #[inline]
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (&*self).read_at(buf, offset)
+    }
+}
+
+/// Reads `buf.len()` bytes at `offset`, or as many as there are until the end of the source
+fn read_full<S: ReadAt + ?Sized>(source: &mut S, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match source.read_at(&mut buf[read..], offset + read as u64) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(read)
+}
+
+/// Reads from a `Read + Seek` stream at any offset, by seeking to it before every read.
+///
+/// # Example
+/// ```rust
+/// use scroll::{PreadAt, SeekReader, LE};
+/// use std::io::Cursor;
+///
+/// let mut source = SeekReader::new(Cursor::new([0xde, 0xad, 0xbe, 0xef]));
+/// assert_eq!(source.pread_with::<u16>(2, LE).unwrap(), 0xefbe);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct SeekReader<R> {
+    inner: R,
+}
+
+impl<R: Read + Seek> SeekReader<R> {
+    /// Wraps the stream `inner`
+    pub fn new(inner: R) -> Self {
+        SeekReader { inner }
+    }
+
+    /// The underlying stream
+    #[inline]
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// The underlying stream, mutably; its position is only meaningful until the next read
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying stream
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read + Seek> ReadAt for SeekReader<R> {
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.read(buf)
+    }
+}
+
+#[derive(Debug)]
+struct Page {
+    // shorter than the page size at the end of the source
+    bytes: Box<[u8]>,
+    last_used: u64,
+}
+
+/// Caches the pages of a [ReadAt](trait.ReadAt.html) source which were read most recently, so
+/// that reading many small values close to each other, e.g. the fields of a header, costs one
+/// read of the source per page instead of one per value.
+///
+/// The source is read in aligned pages of `page_size` bytes, and once `capacity` pages are cached,
+/// the least recently used one is dropped for the next. The cache assumes the source doesn't
+/// change; call [clear](#method.clear) if it does.
+///
+/// # Example
+/// ```rust
+/// use scroll::{PageCache, PreadAt, SeekReader, Uleb128, BE};
+/// use std::io::Cursor;
+///
+/// let bytes = [0x00, 0x00, 0xbe, 0xef, 0xe5, 0x8e, 0x26];
+/// let mut source = PageCache::new(SeekReader::new(Cursor::new(bytes)), 3, 2);
+/// // values can span pages
+/// assert_eq!(source.pread_with::<u16>(2, BE).unwrap(), 0xbeef);
+/// assert_eq!(u64::from(source.pread::<Uleb128>(4).unwrap()), 624485);
+/// ```
+#[derive(Debug)]
+pub struct PageCache<S> {
+    source: S,
+    page_size: usize,
+    capacity: usize,
+    pages: HashMap<u64, Page>,
+    // the index of each cached page by when it was last used, least recently used first
+    recency: BTreeMap<u64, u64>,
+    clock: u64,
+}
+
+impl<S: ReadAt> PageCache<S> {
+    /// Caches up to `capacity` pages of `page_size` bytes of `source`
+    ///
+    /// # Panics
+    /// If `page_size` or `capacity` is zero.
+    pub fn new(source: S, page_size: usize, capacity: usize) -> Self {
+        assert!(page_size > 0, "page size must not be zero");
+        assert!(capacity > 0, "capacity must not be zero");
+        PageCache {
+            source,
+            page_size,
+            capacity,
+            pages: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// The size of the cached pages
+    #[inline]
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// The maximum number of cached pages
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Drops all cached pages, e.g. because the source changed
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.recency.clear();
+    }
+
+    /// The underlying source
+    #[inline]
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Unwraps the underlying source, dropping the cached pages
+    #[inline]
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    fn page(&mut self, index: u64) -> io::Result<&[u8]> {
+        self.clock += 1;
+        match self.pages.get(&index) {
+            Some(page) => {
+                self.recency.remove(&page.last_used);
+            }
+            None if self.pages.len() >= self.capacity => {
+                if let Some((_, lru)) = self.recency.pop_first() {
+                    self.pages.remove(&lru);
+                }
+            }
+            None => {}
+        }
+        let page = match self.pages.entry(index) {
+            Entry::Occupied(page) => page.into_mut(),
+            Entry::Vacant(slot) => {
+                let mut bytes = heap_scratch(self.page_size)?;
+                let offset = index * self.page_size as u64;
+                let len = read_full(&mut self.source, &mut bytes, offset)?;
+                bytes.truncate(len);
+                slot.insert(Page {
+                    bytes: bytes.into_boxed_slice(),
+                    last_used: 0,
+                })
+            }
+        };
+        page.last_used = self.clock;
+        self.recency.insert(self.clock, index);
+        Ok(&page.bytes)
+    }
+}
+
+impl<S: ReadAt> ReadAt for PageCache<S> {
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let page_size = self.page_size;
+        let mut read = 0;
+        while read < buf.len() {
+            let position = offset + read as u64;
+            let page = self.page(position / page_size as u64)?;
+            let start = (position % page_size as u64) as usize;
+            if start >= page.len() {
+                break;
+            }
+            let n = (page.len() - start).min(buf.len() - read);
+            buf[read..read + n].copy_from_slice(&page[start..start + n]);
+            read += n;
+            if page.len() < page_size {
+                break;
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// Shifts the offsets of an error for a value read at `start` to be offsets in the source, unless
+/// they don't fit in a `usize`, as past 4 GiB on 32 bit targets, where they stay relative to `start`
+fn translate(start: u64, err: Error) -> Error {
+    let relative = match &err {
+        Error::BadOffset(offset) => *offset,
+        Error::Context(context) => context.offset(),
+        _ => return err,
+    };
+    let Some(start) = usize::try_from(start)
+        .ok()
+        .filter(|start| start.checked_add(relative).is_some())
+    else {
+        return err;
+    };
+    match err {
+        Error::BadOffset(offset) => Error::BadOffset(start + offset),
+        err => err.offset_by(start),
+    }
+}
+
+/// A [Pread](trait.Pread.html) for [ReadAt](trait.ReadAt.html) sources, like files, which reads
+/// only the bytes a value needs instead of the whole source, so that e.g. a multi-gigabyte core
+/// dump can be parsed without loading it into memory.
+///
+/// Any owned type implementing [`TryFromCtx`](ctx/trait.TryFromCtx.html) can be read, with the
+/// same impl as for `[u8]`: values of a known size are read in one go, and when parsing fails
+/// with `Error::TooBig` or `Error::BadOffset`, as it does for a value which needs more bytes than
+/// were read, it's retried with more bytes until it succeeds or the end of the source is reached.
+/// A corrupt length can therefore make it read up to the end of the source before reporting the
+/// error. Types borrowing from the bytes, like `&str`, can't be read.
+///
+/// Every value is a read of the source; put a [PageCache](struct.PageCache.html) in front of it
+/// when reading many small values. Errors carry offsets into the source, like those of
+/// [Window](struct.Window.html), as long as they fit in a `usize`; on 32 bit targets, errors past
+/// 4 GiB keep the offsets relative to the value read.
+///
+/// # Example
+/// ```rust
+/// use scroll::{PageCache, PreadAt, LE};
+/// use std::fs::File;
+///
+/// let path = std::env::temp_dir().join("scroll_preadat_doc");
+/// std::fs::write(&path, [0x7f, b'E', b'L', b'F', 0x02, 0x01, 0x01, 0x00]).unwrap();
+/// let file = File::open(&path).unwrap();
+/// let mut source = PageCache::new(&file, 4096, 16);
+/// assert_eq!(source.pread_with::<u32>(0, LE).unwrap(), 0x464c457f);
+/// let mut offset = 4;
+/// let class: u8 = source.gread(&mut offset).unwrap();
+/// assert_eq!((class, offset), (2, 5));
+/// assert!(source.pread_with::<u32>(6, LE).is_err());
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub trait PreadAt<Ctx: Copy>: ReadAt {
+    /// Reads a value at `offset` with a default `Ctx`
+    #[inline]
+    fn pread<N: for<'a> TryFromCtx<'a, Ctx, Error = Error>>(
+        &mut self,
+        offset: u64,
+    ) -> error::Result<N>
+    where
+        Ctx: Default,
+    {
+        self.pread_with(offset, Ctx::default())
+    }
+
+    /// Reads a value at `offset` with the given `ctx`
+    #[inline]
+    fn pread_with<N: for<'a> TryFromCtx<'a, Ctx, Error = Error>>(
+        &mut self,
+        offset: u64,
+        ctx: Ctx,
+    ) -> error::Result<N> {
+        let mut ignored = offset;
+        self.gread_with(&mut ignored, ctx)
+    }
+
+    /// Reads a value at `offset` with a default `Ctx`, and updates the offset
+    #[inline]
+    fn gread<N: for<'a> TryFromCtx<'a, Ctx, Error = Error>>(
+        &mut self,
+        offset: &mut u64,
+    ) -> error::Result<N>
+    where
+        Ctx: Default,
+    {
+        self.gread_with(offset, Ctx::default())
+    }
+
+    /// Reads a value at `offset` with the given `ctx`, and updates the offset
+    fn gread_with<N: for<'a> TryFromCtx<'a, Ctx, Error = Error>>(
+        &mut self,
+        offset: &mut u64,
+        ctx: Ctx,
+    ) -> error::Result<N> {
+        let start = *offset;
+        // most values fit in the first read, which therefore goes to the stack
+        let mut first = [0u8; INITIAL_READ];
+        let read = read_full(self, &mut first, start)?;
+        let mut buf = Vec::new();
+        let mut want = INITIAL_READ;
+        loop {
+            let bytes = if buf.is_empty() {
+                &first[..read]
+            } else {
+                &buf[..]
+            };
+            let at_end = bytes.len() < want;
+            match N::try_from_ctx(bytes, ctx) {
+                Ok((n, size)) => {
+                    *offset = start + size as u64;
+                    return Ok(n);
+                }
+                Err(err) if !at_end => match *err.root_cause() {
+                    // the bytes the failing read was missing, if it read up to the end
+                    Error::TooBig { size, len } => {
+                        let missing = size.saturating_sub(len);
+                        want = want.saturating_mul(2).max(want.saturating_add(missing));
+                    }
+                    Error::BadOffset(_) => want = want.saturating_mul(2),
+                    _ => return Err(translate(start, err)),
+                },
+                Err(err) => return Err(translate(start, err)),
+            }
+            if buf.is_empty() {
+                buf.extend_from_slice(&first);
+            }
+            let filled = buf.len();
+            buf.try_reserve_exact(want - filled)
+                .map_err(|err| io::Error::new(io::ErrorKind::OutOfMemory, err))?;
+            buf.resize(want, 0);
+            let read = read_full(self, &mut buf[filled..], start + filled as u64)?;
+            buf.truncate(filled + read);
+        }
+    }
+}
+
+/// Types that implement `ReadAt` get methods defined in `PreadAt`
+/// for free.
+impl<Ctx: Copy, S: ReadAt + ?Sized> PreadAt<Ctx> for S {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BE, LE, Uleb128};
+    use std::io::Cursor;
+
+    /// A source which counts its reads
+    struct Counted<'a> {
+        bytes: &'a [u8],
+        reads: usize,
+    }
+
+    impl ReadAt for Counted<'_> {
+        fn read_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+            self.reads += 1;
+            let rest = self.bytes.get(offset as usize..).unwrap_or_default();
+            let n = rest.len().min(buf.len());
+            buf[..n].copy_from_slice(&rest[..n]);
+            Ok(n)
+        }
+    }
+
+    fn bytes() -> Vec<u8> {
+        let mut bytes = vec![0u8; 1000];
+        bytes[..4].copy_from_slice(&0xdeadbeefu32.to_be_bytes());
+        // a Uleb128 straddling the pages at 256
+        bytes[250..260].copy_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 1]);
+        bytes[996..].copy_from_slice(&0xcafebabeu32.to_le_bytes());
+        bytes
+    }
+
+    fn check<S: ReadAt>(source: &mut S) {
+        assert_eq!(source.pread_with::<u32>(0, BE).unwrap(), 0xdeadbeef);
+        assert_eq!(source.pread_with::<u32>(996, LE).unwrap(), 0xcafebabe);
+        let mut offset = 250;
+        let n: Uleb128 = source.gread(&mut offset).unwrap();
+        assert_eq!((u64::from(n), offset), (u64::MAX, 260));
+        // a value larger than the first read
+        let mut offset = 4;
+        let block: [u16; 300] = source.gread_with(&mut offset, LE).unwrap();
+        assert_eq!((block[127], offset), (0x01ff, 604));
+        match source.pread_with::<u64>(996, LE) {
+            Err(Error::TooBig { size: 8, len: 4 }) => (),
+            err => panic!("unexpected {err:?}"),
+        }
+        assert!(source.pread_with::<u8>(1000, LE).is_err());
+    }
+
+    #[test]
+    fn read_at_sources() {
+        let bytes = bytes();
+        check(&mut SeekReader::new(Cursor::new(&bytes)));
+        check(&mut PageCache::new(
+            SeekReader::new(Cursor::new(&bytes)),
+            256,
+            2,
+        ));
+        check(&mut PageCache::new(
+            SeekReader::new(Cursor::new(&bytes)),
+            7,
+            1,
+        ));
+
+        let path = std::env::temp_dir().join("scroll_read_at_sources");
+        std::fs::write(&path, &bytes).unwrap();
+        let file = File::open(&path).unwrap();
+        check(&mut &file);
+        check(&mut PageCache::new(&file, 4096, 4));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn page_cache_reuses_pages() {
+        let bytes = [0u8; 1536];
+        let mut cache = PageCache::new(
+            Counted {
+                bytes: &bytes,
+                reads: 0,
+            },
+            512,
+            2,
+        );
+        for offset in [0, 4, 8, 100, 510, 600, 2] {
+            cache.pread_with::<u32>(offset, LE).unwrap();
+        }
+        assert_eq!(cache.get_ref().reads, 2);
+        // the page at 0 was used last, so the one at 512 is dropped for the next one
+        cache.pread_with::<u32>(1100, LE).unwrap();
+        cache.pread_with::<u32>(0, LE).unwrap();
+        assert_eq!(cache.get_ref().reads, 3);
+        cache.pread_with::<u32>(600, LE).unwrap();
+        assert_eq!(cache.get_ref().reads, 4);
+        cache.clear();
+        cache.pread_with::<u32>(600, LE).unwrap();
+        assert_eq!(cache.get_ref().reads, 5);
+    }
+
+    #[test]
+    fn translate_offsets() {
+        assert!(matches!(
+            translate(8, Error::BadOffset(1)),
+            Error::BadOffset(9)
+        ));
+        let Error::Context(context) = translate(8, Error::BadOffset(1).at_index(0, 2)) else {
+            panic!("no context");
+        };
+        assert_eq!(context.offset(), 10);
+        // offsets which don't fit in a usize stay relative to the value
+        assert!(matches!(
+            translate(u64::MAX, Error::BadOffset(1)),
+            Error::BadOffset(1)
+        ));
+    }
+}
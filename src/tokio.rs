@@ -0,0 +1,105 @@
+//! Async counterparts of [IOread](../trait.IOread.html), [BufIOread](../trait.BufIOread.html) and
+//! [IOwrite](../trait.IOwrite.html) for the `AsyncRead`, `AsyncBufRead` and `AsyncWrite` streams
+//! of tokio, enabled by the `tokio` feature.
+//!
+//! # Example
+//! ```rust
+//! use scroll::tokio::{AsyncBufIOread, AsyncIOread, AsyncIOwrite};
+//! use scroll::{Uleb128, BE};
+//! use tokio::io::BufReader;
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let (mut client, server) = tokio::io::duplex(64);
+//! client.iowrite_with(0xdeadbeefu32, BE).await.unwrap();
+//! client.try_iowrite(Uleb128::from(624485)).await.unwrap();
+//! let mut server = BufReader::new(server);
+//! assert_eq!(server.ioread_with::<u32>(BE).await.unwrap(), 0xdeadbeef);
+//! let n: Uleb128 = server.try_ioread().await.unwrap();
+//! assert_eq!(u64::from(n), 624485);
+//! # });
+//! ```
+
+use std::io::Result;
+
+use ::tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+async fn read_exact<R: AsyncRead + Unpin + ?Sized>(reader: &mut R, buf: &mut [u8]) -> Result<()> {
+    reader.read_exact(buf).await.map(drop)
+}
+
+async fn write_all<W: AsyncWrite + Unpin + ?Sized>(writer: &mut W, buf: &[u8]) -> Result<()> {
+    writer.write_all(buf).await
+}
+
+async_io_traits!("tokio's");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ctx::{FromCtx, IntoCtx, SizeWith};
+    use crate::{BE, Endian, LE, Pread, Uleb128};
+    use ::tokio::io::BufReader;
+    use std::io::ErrorKind;
+
+    /// A value larger than the scratch buffer on the stack
+    #[derive(Debug, PartialEq)]
+    struct Block([u8; 300]);
+
+    impl FromCtx<Endian> for Block {
+        fn from_ctx(src: &[u8], _: Endian) -> Self {
+            Block(src.pread(0).unwrap())
+        }
+    }
+
+    impl IntoCtx<Endian> for &Block {
+        fn into_ctx(self, dst: &mut [u8], _: Endian) {
+            dst.copy_from_slice(&self.0);
+        }
+    }
+
+    impl SizeWith<Endian> for &Block {
+        fn size_with(_: &Endian) -> usize {
+            300
+        }
+    }
+
+    impl SizeWith<Endian> for Block {
+        fn size_with(_: &Endian) -> usize {
+            300
+        }
+    }
+
+    #[::tokio::test]
+    async fn async_io_duplex() {
+        let block = Block(core::array::from_fn(|i| i as u8));
+        // a small pipe, so that the writer has to wait for the reader
+        let (mut client, server) = ::tokio::io::duplex(16);
+        let writer = ::tokio::spawn(async move {
+            let block = Block(core::array::from_fn(|i| i as u8));
+            client.iowrite_with(0xdeadbeefu32, BE).await.unwrap();
+            client.iowrite_with(&block, LE).await.unwrap();
+            for n in [0, 128, u64::MAX] {
+                client.try_iowrite(Uleb128::from(n)).await.unwrap();
+            }
+            assert_eq!(client.try_iowrite(&[0xab; 40][..]).await.unwrap(), 40);
+            client.iowrite(0x80u8).await.unwrap();
+        });
+        let mut server = BufReader::with_capacity(4, server);
+        assert_eq!(server.ioread_with::<u32>(BE).await.unwrap(), 0xdeadbeef);
+        assert_eq!(server.ioread_with::<Block>(LE).await.unwrap(), block);
+        for n in [0, 128, u64::MAX] {
+            assert_eq!(u64::from(server.try_ioread::<Uleb128>().await.unwrap()), n);
+        }
+        assert_eq!(
+            server.ioread_with::<u64>(LE).await.unwrap(),
+            0xabab_abab_abab_abab
+        );
+        server.ioread_with::<Block>(LE).await.unwrap_err();
+        writer.await.unwrap();
+        // the truncated Uleb128 at the end of the stream
+        let mut server = BufReader::new(&[0x80u8][..]);
+        assert!(server.try_ioread::<Uleb128>().await.is_err());
+        let err = server.ioread::<u8>().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+}
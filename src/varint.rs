@@ -0,0 +1,356 @@
+use core::convert::{AsRef, From};
+use core::result;
+
+use crate::ctx::{MeasureWith, TryFromCtx, TryIntoCtx};
+use crate::leb128::{
+    CONTINUATION_BIT, mask_continuation, read_unsigned, unsigned_size, write_unsigned,
+};
+use crate::{Pread, Pwrite, error};
+
+macro_rules! varint_common_impl {
+    ($name:ident, $typ:ty) => {
+        impl $name {
+            #[inline]
+            #[doc = concat!("Return how many bytes this ", stringify!($name), " takes up in memory")]
+            pub fn size(&self) -> usize {
+                self.count
+            }
+            #[inline]
+            #[doc = concat!("Read a variable length `", stringify!($typ), "` from `bytes` at `offset`")]
+            pub fn read(bytes: &[u8], offset: &mut usize) -> error::Result<$typ> {
+                let tmp = bytes.pread::<$name>(*offset)?;
+                *offset += tmp.size();
+                Ok(tmp.into())
+            }
+            #[inline]
+            #[doc = concat!("Write `value` as a variable length `", stringify!($typ), "` into `bytes` at `offset`, returning the number of bytes written")]
+            pub fn write(bytes: &mut [u8], offset: &mut usize, value: $typ) -> error::Result<usize> {
+                bytes.gwrite($name::from(value), offset)
+            }
+        }
+
+        impl AsRef<$typ> for $name {
+            fn as_ref(&self) -> &$typ {
+                &self.value
+            }
+        }
+
+        impl From<$name> for $typ {
+            #[inline]
+            fn from(varint: $name) -> $typ {
+                varint.value
+            }
+        }
+
+        impl<Ctx> MeasureWith<Ctx> for $name {
+            #[inline]
+            fn measure_with(&self, _ctx: &Ctx) -> usize {
+                self.count
+            }
+        }
+
+        impl TryIntoCtx for &$name {
+            type Error = error::Error;
+            #[inline]
+            fn try_into_ctx(self, dst: &mut [u8], ctx: ()) -> error::Result<usize> {
+                (*self).try_into_ctx(dst, ctx)
+            }
+        }
+    };
+}
+
+macro_rules! zigzag_impl {
+    ($name:ident, $typ:ty, $unsigned:ty, $bits:expr) => {
+        #[derive(Debug, PartialEq, Eq, Copy, Clone)]
+        #[doc = concat!("A zigzag encoded `", stringify!($typ), "`, as used by protobuf's `sint", $bits, "`")]
+        ///
+        /// The value is mapped to an unsigned integer so that small magnitudes, positive or
+        /// negative, encode to few bytes (`0 => 0, -1 => 1, 1 => 2, -2 => 3, ...`), which is then
+        /// written as an unsigned leb128.
+        pub struct $name {
+            value: $typ,
+            count: usize,
+        }
+
+        varint_common_impl!($name, $typ);
+
+        impl From<$typ> for $name {
+            #[inline]
+            fn from(value: $typ) -> $name {
+                let encoded = ((value << 1) ^ (value >> ($bits - 1))) as $unsigned;
+                $name {
+                    value,
+                    count: unsigned_size(u128::from(encoded)),
+                }
+            }
+        }
+
+        impl<'a> TryFromCtx<'a> for $name {
+            type Error = error::Error;
+            #[inline]
+            fn try_from_ctx(src: &'a [u8], _ctx: ()) -> result::Result<(Self, usize), Self::Error> {
+                let (encoded, count) = read_unsigned(src, $bits, false)?;
+                let encoded = encoded as $unsigned;
+                let value = (encoded >> 1) as $typ ^ -((encoded & 1) as $typ);
+                Ok(($name { value, count }, count))
+            }
+        }
+
+        impl TryIntoCtx for $name {
+            type Error = error::Error;
+            #[inline]
+            fn try_into_ctx(self, dst: &mut [u8], _ctx: ()) -> error::Result<usize> {
+                let encoded = ((self.value << 1) ^ (self.value >> ($bits - 1))) as $unsigned;
+                write_unsigned(dst, u128::from(encoded), self.count, $bits)
+            }
+        }
+    };
+}
+
+zigzag_impl!(ZigZag32, i32, u32, 32);
+zigzag_impl!(ZigZag64, i64, u64, 64);
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+/// A QUIC variable length integer (RFC 9000, section 16)
+///
+/// The two most significant bits of the first byte give the length of the big endian encoding
+/// (1, 2, 4 or 8 bytes), leaving 62 bits for the value.
+pub struct QuicVarint {
+    value: u64,
+    count: usize,
+}
+
+impl QuicVarint {
+    /// The largest value a QUIC varint can hold
+    pub const MAX: u64 = (1 << 62) - 1;
+}
+
+varint_common_impl!(QuicVarint, u64);
+
+impl From<u64> for QuicVarint {
+    #[inline]
+    fn from(value: u64) -> QuicVarint {
+        let count = match value {
+            0..=0x3f => 1,
+            0x40..=0x3fff => 2,
+            0x4000..=0x3fff_ffff => 4,
+            _ => 8,
+        };
+        QuicVarint { value, count }
+    }
+}
+
+impl<'a> TryFromCtx<'a> for QuicVarint {
+    type Error = error::Error;
+    #[inline]
+    fn try_from_ctx(src: &'a [u8], _ctx: ()) -> result::Result<(Self, usize), Self::Error> {
+        let first: u8 = src.pread(0)?;
+        let count = 1 << (first >> 6);
+        if count > src.len() {
+            return Err(error::Error::TooBig {
+                size: count,
+                len: src.len(),
+            });
+        }
+        let value = src[1..count]
+            .iter()
+            .fold(u64::from(first & 0x3f), |value, byte| {
+                (value << 8) | u64::from(*byte)
+            });
+        Ok((QuicVarint { value, count }, count))
+    }
+}
+
+/// Writes exactly `self.count` bytes, since QUIC permits non-minimal encodings
+impl TryIntoCtx for QuicVarint {
+    type Error = error::Error;
+    #[inline]
+    fn try_into_ctx(self, dst: &mut [u8], _ctx: ()) -> error::Result<usize> {
+        if self.value >> (8 * self.count - 2) != 0 {
+            return Err(error::Error::BadInput {
+                size: self.count,
+                msg: "value too large for a quic varint",
+            });
+        }
+        if self.count > dst.len() {
+            return Err(error::Error::TooBig {
+                size: self.count,
+                len: dst.len(),
+            });
+        }
+        let bytes = self.value.to_be_bytes();
+        dst[..self.count].copy_from_slice(&bytes[8 - self.count..]);
+        dst[0] |= (self.count.trailing_zeros() as u8) << 6;
+        Ok(self.count)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+/// A big endian variable length quantity, as used by MIDI files or git packs
+///
+/// Like leb128 each byte holds 7 bits of the value with the high bit set on all but the last
+/// byte, but the most significant group comes first.
+pub struct Vlq {
+    value: u64,
+    count: usize,
+}
+
+impl Vlq {
+    /// The maximum number of bytes an encoding of a vlq may take up
+    pub const MAX_SIZE: usize = 10;
+}
+
+varint_common_impl!(Vlq, u64);
+
+impl From<u64> for Vlq {
+    #[inline]
+    fn from(value: u64) -> Vlq {
+        Vlq {
+            value,
+            count: unsigned_size(u128::from(value)),
+        }
+    }
+}
+
+impl<'a> TryFromCtx<'a> for Vlq {
+    type Error = error::Error;
+    #[inline]
+    fn try_from_ctx(src: &'a [u8], _ctx: ()) -> result::Result<(Self, usize), Self::Error> {
+        let mut value: u64 = 0;
+        let mut count = 0;
+        loop {
+            let byte: u8 = src.pread(count)?;
+            count += 1;
+            if count > Vlq::MAX_SIZE || value >> 57 != 0 {
+                return Err(error::Error::BadInput {
+                    size: src.len(),
+                    msg: "vlq overflows a u64",
+                });
+            }
+            value = (value << 7) | u64::from(mask_continuation(byte));
+            if byte & CONTINUATION_BIT == 0 {
+                return Ok((Vlq { value, count }, count));
+            }
+        }
+    }
+}
+
+/// Leading `0x80` padding of a vlq that was read in is kept when writing it back out
+impl TryIntoCtx for Vlq {
+    type Error = error::Error;
+    #[inline]
+    fn try_into_ctx(self, dst: &mut [u8], _ctx: ()) -> error::Result<usize> {
+        if self.count > dst.len() {
+            return Err(error::Error::TooBig {
+                size: self.count,
+                len: dst.len(),
+            });
+        }
+        let last = self.count - 1;
+        for (i, byte) in dst[..self.count].iter_mut().enumerate() {
+            *byte = mask_continuation((self.value >> (7 * (last - i))) as u8);
+            if i < last {
+                *byte |= CONTINUATION_BIT;
+            }
+        }
+        Ok(self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Pread, Pwrite};
+    use super::{QuicVarint, Vlq, ZigZag32, ZigZag64};
+
+    #[test]
+    fn zigzag() {
+        for (value, encoded) in [(0i64, 0u8), (-1, 1), (1, 2), (-2, 3), (63, 126), (-64, 127)] {
+            let zigzag = ZigZag64::from(value);
+            assert_eq!(zigzag.size(), 1);
+            let mut buf = [0u8; 1];
+            buf.pwrite(zigzag, 0).unwrap();
+            assert_eq!(buf[0], encoded);
+            assert_eq!(buf.pread::<ZigZag64>(0).unwrap(), zigzag);
+        }
+        let mut buf = [0u8; 10];
+        for value in [i32::MIN, -300, 300, i32::MAX] {
+            let offset = &mut 0;
+            assert_eq!(ZigZag32::write(&mut buf, offset, value).unwrap(), *offset);
+            assert_eq!(ZigZag32::read(&buf, &mut 0).unwrap(), value);
+        }
+        for value in [i64::MIN, i64::MAX] {
+            buf.pwrite(ZigZag64::from(value), 0).unwrap();
+            assert_eq!(ZigZag64::read(&buf, &mut 0).unwrap(), value);
+        }
+        // 2^32 does not fit a sint32
+        let bytes = [0x80u8, 0x80, 0x80, 0x80, 0x10];
+        assert!(bytes.pread::<ZigZag32>(0).is_err());
+    }
+
+    #[test]
+    fn quic_varint() {
+        // examples from RFC 9000, appendix A.1
+        let bytes = [0xc2u8, 0x19, 0x7c, 0x5e, 0xff, 0x14, 0xe8, 0x8c];
+        let varint = bytes.pread::<QuicVarint>(0).unwrap();
+        assert_eq!(u64::from(varint), 151_288_809_941_952_652);
+        assert_eq!(varint.size(), 8);
+        let mut buf = [0u8; 8];
+        buf.pwrite(varint, 0).unwrap();
+        assert_eq!(buf, bytes);
+        for (bytes, value) in [
+            (&[0x9du8, 0x7f, 0x3e, 0x7d][..], 494_878_333u64),
+            (&[0x7b, 0xbd], 15293),
+            (&[0x25], 37),
+            (&[0x40, 0x25], 37),
+        ] {
+            let varint = bytes.pread::<QuicVarint>(0).unwrap();
+            assert_eq!(u64::from(varint), value);
+            assert_eq!(varint.size(), bytes.len());
+            let mut buf = [0u8; 8];
+            assert_eq!(buf.pwrite(varint, 0).unwrap(), bytes.len());
+            assert_eq!(&buf[..bytes.len()], bytes);
+        }
+        assert_eq!(QuicVarint::from(37).size(), 1);
+        assert_eq!(QuicVarint::from(15293).size(), 2);
+        assert_eq!(QuicVarint::from(QuicVarint::MAX).size(), 8);
+        assert!(
+            buf.pwrite(QuicVarint::from(QuicVarint::MAX + 1), 0)
+                .is_err()
+        );
+        assert!([0x80u8, 0x00].pread::<QuicVarint>(0).is_err());
+    }
+
+    #[test]
+    fn vlq() {
+        // examples from the MIDI specification
+        for (value, bytes) in [
+            (0u64, &[0x00u8][..]),
+            (0x7f, &[0x7f]),
+            (0x80, &[0x81, 0x00]),
+            (0x2000, &[0xc0, 0x00]),
+            (0x3fff, &[0xff, 0x7f]),
+            (0x4000, &[0x81, 0x80, 0x00]),
+            (0x0fff_ffff, &[0xff, 0xff, 0xff, 0x7f]),
+        ] {
+            let vlq = Vlq::from(value);
+            assert_eq!(vlq.size(), bytes.len());
+            let mut buf = [0u8; 4];
+            assert_eq!(buf.pwrite(vlq, 0).unwrap(), bytes.len());
+            assert_eq!(&buf[..bytes.len()], bytes);
+            assert_eq!(bytes.pread::<Vlq>(0).unwrap(), vlq);
+        }
+        let mut buf = [0u8; 10];
+        Vlq::write(&mut buf, &mut 0, u64::MAX).unwrap();
+        assert_eq!(buf[0], 0x81);
+        assert_eq!(Vlq::read(&buf, &mut 0).unwrap(), u64::MAX);
+        // u64::MAX + 1
+        let bytes = [0x82u8, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00];
+        assert!(bytes.pread::<Vlq>(0).is_err());
+        let padded = [0x80u8, 0x80, 0x01];
+        let vlq = padded.pread::<Vlq>(0).unwrap();
+        assert_eq!(u64::from(vlq), 1);
+        let mut buf = [0xffu8; 3];
+        buf.pwrite(vlq, 0).unwrap();
+        assert_eq!(buf, padded);
+    }
+}
@@ -0,0 +1,343 @@
+use core::convert::{AsMut, AsRef};
+
+use crate::ctx::{TryFromCtx, TryIntoCtx};
+use crate::error::{self, Error};
+
+/// A bounded view of `len` bytes at `base` in some larger buffer, e.g. a section inside a file.
+///
+/// Reads and writes through a `Window` take offsets relative to the start of the window and can
+/// never touch bytes outside of it. Errors however are reported in terms of the original buffer:
+/// an `Error::BadOffset` carries the absolute offset of the failed access, so a failure deep in a
+/// nested format still points at the right byte of the file, and so does the offset of an
+/// `Error::Context`. The error wrapped in an `Error::Context` is left untouched, including a
+/// `BadOffset`, as it is relative to the innermost value which failed rather than to the window;
+/// the context's offset is the absolute one. `Error::TooBig` is left untouched too, as its `len`
+/// is the number of bytes that were left in the window.
+///
+/// `Window` gets the [Pread](trait.Pread.html) and [Pwrite](trait.Pwrite.html) methods from
+/// [WindowPread](trait.WindowPread.html) and [WindowPwrite](trait.WindowPwrite.html) rather than
+/// from the traits themselves, so every existing `TryFromCtx`/`TryIntoCtx` impl for `[u8]` can be
+/// used with it.
+///
+/// # Example
+/// ```rust
+/// use scroll::{Error, Window, WindowPread, BE};
+/// let file = [0x00, 0x00, 0xde, 0xad, 0xbe, 0xef, 0x00];
+/// let section = Window::new(&file[..], 2, 4).unwrap();
+/// assert_eq!(section.pread_with::<u16>(2, BE).unwrap(), 0xbeef);
+/// // a record inside the section
+/// let record = section.window(2, 2).unwrap();
+/// assert_eq!(record.base(), 4);
+/// match record.pread_with::<u16>(3, BE) {
+///     Err(Error::BadOffset(offset)) => assert_eq!(offset, 7),
+///     _ => unreachable!(),
+/// }
+/// // reading past the end of the window fails, even though `file` has more bytes
+/// assert!(record.pread_with::<u32>(0, BE).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Window<T> {
+    data: T,
+    base: usize,
+    len: usize,
+}
+
+impl<T: AsRef<[u8]>> Window<T> {
+    /// Creates a window over `len` bytes of `data` starting at `base`
+    pub fn new(data: T, base: usize, len: usize) -> error::Result<Self> {
+        let available = data.as_ref().len();
+        if base > available {
+            return Err(Error::BadOffset(base));
+        }
+        if len > available - base {
+            return Err(Error::TooBig {
+                size: len,
+                len: available - base,
+            });
+        }
+        Ok(Window { data, base, len })
+    }
+
+    /// The offset of this window in the underlying buffer
+    #[inline]
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    /// The length of this window
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether this window is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The bytes of this window
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data.as_ref()[self.base..self.base + self.len]
+    }
+
+    /// Returns the underlying buffer
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+
+    /// Creates a window nested in this one, `len` bytes at the relative `offset`. The nested
+    /// window still reports offsets relative to the underlying buffer.
+    pub fn window(&self, offset: usize, len: usize) -> error::Result<Window<&[u8]>> {
+        if offset > self.len {
+            return Err(Error::BadOffset(self.base + offset));
+        }
+        if len > self.len - offset {
+            return Err(Error::TooBig {
+                size: len,
+                len: self.len - offset,
+            });
+        }
+        Ok(Window {
+            data: self.data.as_ref(),
+            base: self.base + offset,
+            len,
+        })
+    }
+
+    #[inline]
+    fn translate(&self, start: usize, err: Error) -> Error {
+        match err {
+            Error::BadOffset(offset) => Error::BadOffset(self.base + start + offset),
+            err => err.offset_by(self.base + start),
+        }
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Window<T> {
+    /// The bytes of this window, mutably
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        let (base, len) = (self.base, self.len);
+        &mut self.data.as_mut()[base..base + len]
+    }
+}
+
+/// The [Pread](trait.Pread.html) methods of a [Window](struct.Window.html), with the same call
+/// shape, e.g. `window.pread_with::<u16>(0, BE)`.
+///
+/// `Window` can't implement `Pread` itself: `Pread` hands a value the bytes it is read from as a
+/// `&'a Self`, so it would only read types with an impl for a `Window`, and the rest of a window
+/// after an offset isn't a `Window` which can be borrowed for `'a` anyway. These methods instead
+/// read every existing `TryFromCtx` impl for `[u8]`, at offsets relative to the window, and
+/// translate the errors to absolute offsets.
+pub trait WindowPread<Ctx: Copy> {
+    /// Reads a value at the relative `offset` with a default `Ctx`
+    #[inline]
+    fn pread<'a, N: TryFromCtx<'a, Ctx, Error = Error>>(&'a self, offset: usize) -> error::Result<N>
+    where
+        Ctx: Default,
+    {
+        self.pread_with(offset, Ctx::default())
+    }
+
+    /// Reads a value at the relative `offset` with the given `ctx`
+    #[inline]
+    fn pread_with<'a, N: TryFromCtx<'a, Ctx, Error = Error>>(
+        &'a self,
+        offset: usize,
+        ctx: Ctx,
+    ) -> error::Result<N> {
+        let mut ignored = offset;
+        self.gread_with(&mut ignored, ctx)
+    }
+
+    /// Reads a value at the relative `offset` with a default `Ctx`, and updates the offset
+    #[inline]
+    fn gread<'a, N: TryFromCtx<'a, Ctx, Error = Error>>(
+        &'a self,
+        offset: &mut usize,
+    ) -> error::Result<N>
+    where
+        Ctx: Default,
+    {
+        self.gread_with(offset, Ctx::default())
+    }
+
+    /// Reads a value at the relative `offset` with the given `ctx`, and updates the offset
+    fn gread_with<'a, N: TryFromCtx<'a, Ctx, Error = Error>>(
+        &'a self,
+        offset: &mut usize,
+        ctx: Ctx,
+    ) -> error::Result<N>;
+}
+
+impl<Ctx: Copy, T: AsRef<[u8]>> WindowPread<Ctx> for Window<T> {
+    fn gread_with<'a, N: TryFromCtx<'a, Ctx, Error = Error>>(
+        &'a self,
+        offset: &mut usize,
+        ctx: Ctx,
+    ) -> error::Result<N> {
+        let start = *offset;
+        let bytes = self.as_slice();
+        if start > bytes.len() {
+            return Err(Error::BadOffset(self.base + start));
+        }
+        match N::try_from_ctx(&bytes[start..], ctx) {
+            Ok((n, size)) => {
+                *offset += size;
+                Ok(n)
+            }
+            Err(err) => Err(self.translate(start, err)),
+        }
+    }
+}
+
+/// The [Pwrite](trait.Pwrite.html) methods of a [Window](struct.Window.html), with the same call
+/// shape; see [WindowPread](trait.WindowPread.html) for why `Window` doesn't implement `Pwrite`.
+pub trait WindowPwrite<Ctx: Copy> {
+    /// Writes `n` at the relative `offset` with a default `Ctx`
+    #[inline]
+    fn pwrite<N: TryIntoCtx<Ctx, Error = Error>>(
+        &mut self,
+        n: N,
+        offset: usize,
+    ) -> error::Result<usize>
+    where
+        Ctx: Default,
+    {
+        self.pwrite_with(n, offset, Ctx::default())
+    }
+
+    /// Writes `n` at the relative `offset` with the given `ctx`
+    fn pwrite_with<N: TryIntoCtx<Ctx, Error = Error>>(
+        &mut self,
+        n: N,
+        offset: usize,
+        ctx: Ctx,
+    ) -> error::Result<usize>;
+
+    /// Writes `n` at the relative `offset` with a default `Ctx`, and updates the offset
+    #[inline]
+    fn gwrite<N: TryIntoCtx<Ctx, Error = Error>>(
+        &mut self,
+        n: N,
+        offset: &mut usize,
+    ) -> error::Result<usize>
+    where
+        Ctx: Default,
+    {
+        self.gwrite_with(n, offset, Ctx::default())
+    }
+
+    /// Writes `n` at the relative `offset` with the given `ctx`, and updates the offset
+    #[inline]
+    fn gwrite_with<N: TryIntoCtx<Ctx, Error = Error>>(
+        &mut self,
+        n: N,
+        offset: &mut usize,
+        ctx: Ctx,
+    ) -> error::Result<usize> {
+        let o = *offset;
+        self.pwrite_with(n, o, ctx).inspect(|size| {
+            *offset += size;
+        })
+    }
+}
+
+impl<Ctx: Copy, T: AsRef<[u8]> + AsMut<[u8]>> WindowPwrite<Ctx> for Window<T> {
+    fn pwrite_with<N: TryIntoCtx<Ctx, Error = Error>>(
+        &mut self,
+        n: N,
+        offset: usize,
+        ctx: Ctx,
+    ) -> error::Result<usize> {
+        if offset > self.len {
+            return Err(Error::BadOffset(self.base + offset));
+        }
+        let dst = &mut self.as_mut_slice()[offset..];
+        n.try_into_ctx(dst, ctx)
+            .map_err(|err| self.translate(offset, err))
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Window<T> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BE, LE};
+
+    #[test]
+    fn window_bounds() {
+        let bytes = [0u8; 8];
+        assert!(matches!(
+            Window::new(&bytes[..], 9, 0),
+            Err(Error::BadOffset(9))
+        ));
+        assert!(matches!(
+            Window::new(&bytes[..], 4, 5),
+            Err(Error::TooBig { size: 5, len: 4 })
+        ));
+        let window = Window::new(&bytes[..], 4, 4).unwrap();
+        assert_eq!(window.len(), 4);
+        assert!(window.window(2, 3).is_err());
+        assert!(Window::new(&bytes[..], 8, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn window_read() {
+        let bytes = [0xff, 0xff, 0x01, 0x02, 0x03, 0x04, 0xff, 0xff];
+        let window = Window::new(&bytes[..], 2, 4).unwrap();
+        assert_eq!(window.as_slice(), &bytes[2..6]);
+        assert_eq!(window.pread_with::<u32>(0, BE).unwrap(), 0x01020304);
+        let offset = &mut 1;
+        assert_eq!(window.gread::<u8>(offset).unwrap(), 2);
+        assert_eq!(window.gread_with::<u16>(offset, LE).unwrap(), 0x0403);
+        assert_eq!(*offset, 4);
+        assert!(matches!(
+            window.pread_with::<u8>(5, LE),
+            Err(Error::BadOffset(7))
+        ));
+        assert!(matches!(
+            window.pread_with::<u64>(0, LE),
+            Err(Error::TooBig { size: 8, len: 4 })
+        ));
+        let nested = window.window(1, 2).unwrap();
+        assert_eq!(nested.base(), 3);
+        assert_eq!(nested.pread_with::<u16>(0, BE).unwrap(), 0x0203);
+        assert!(matches!(
+            nested.pread_with::<&[u8]>(3, 0),
+            Err(Error::BadOffset(6))
+        ));
+        // the context is made absolute, the error it wraps isn't
+        #[cfg(feature = "alloc")]
+        match window.pread_with::<[u16; 3]>(0, LE) {
+            Err(Error::Context(context)) => {
+                assert_eq!(context.offset(), 6);
+                assert!(matches!(context.error(), Error::TooBig { size: 2, len: 0 }));
+            }
+            err => panic!("unexpected {err:?}"),
+        }
+    }
+
+    #[test]
+    fn window_write() {
+        let mut bytes = [0u8; 6];
+        let mut window = Window::new(&mut bytes[..], 2, 3).unwrap();
+        let offset = &mut 0;
+        window.gwrite_with(0xbeefu16, offset, BE).unwrap();
+        window.gwrite(0x7fu8, offset).unwrap();
+        assert!(window.gwrite(0u8, offset).is_err());
+        assert!(matches!(window.pwrite(0u8, 4), Err(Error::BadOffset(6))));
+        assert_eq!(window.as_slice(), [0xbe, 0xef, 0x7f]);
+        assert_eq!(bytes, [0, 0, 0xbe, 0xef, 0x7f, 0]);
+    }
+}
@@ -0,0 +1,323 @@
+use alloc::vec::Vec;
+use core::cmp;
+
+use crate::ctx::TryIntoCtx;
+use crate::error::{self, Error};
+
+/// A `Vec<u8>` backed writer which grows as needed, so that `TryIntoCtx` types can be serialized
+/// without computing their size beforehand.
+///
+/// `VecWriter` mirrors the [Pwrite](trait.Pwrite.html) methods. Writing past the end extends the
+/// buffer, and any gap between the old end and the write offset is zero-filled. As the size of a
+/// value isn't known before writing it, it is written into a speculatively grown buffer and retried
+/// with more room whenever it fails with `Error::TooBig` or `Error::BadOffset`; this is why the
+/// written values must be `Clone`. Write references (which are `Copy`) for types that aren't.
+///
+/// # Example
+/// ```rust
+/// use scroll::{VecWriter, BE};
+/// let mut writer = VecWriter::new();
+/// let offset = &mut 0;
+/// writer.gwrite_with(0xdeadu16, offset, BE).unwrap();
+/// writer.gwrite("hello", offset).unwrap();
+/// writer.pwrite_with(0xefu8, 8, BE).unwrap();
+/// assert_eq!(writer.into_inner(), b"\xde\xadhello\0\xef");
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct VecWriter {
+    buf: Vec<u8>,
+}
+
+impl VecWriter {
+    /// Creates an empty writer
+    #[inline]
+    pub fn new() -> Self {
+        VecWriter { buf: Vec::new() }
+    }
+
+    /// Creates an empty writer with room for `capacity` bytes
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        VecWriter {
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// The number of bytes written so far, including any zero-filled gaps
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Whether nothing was written yet
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// The bytes written so far
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Returns the written bytes
+    #[inline]
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Writes `n` at `offset` with a default `Ctx`
+    #[inline]
+    pub fn pwrite<N: TryIntoCtx<Ctx, Error = Error> + Clone, Ctx: Copy + Default>(
+        &mut self,
+        n: N,
+        offset: usize,
+    ) -> error::Result<usize> {
+        self.pwrite_with(n, offset, Ctx::default())
+    }
+
+    /// Writes `n` at `offset` with the given `ctx`, growing the buffer as needed
+    ///
+    /// The value is written into a slice which starts small and grows until it fits. Retrying
+    /// stops as soon as a failure doesn't look like it was caused by the end of the slice, i.e. a
+    /// `TooBig` which comes back the same with more room, or a `BadOffset` inside of it; a `TooBig`
+    /// with nothing left, which element by element writes fail with however far they got, is only
+    /// retried until the slice reaches 1 MiB past the end of the buffer. Failing to allocate more
+    /// room is an `Error::BadInput`. If the write fails, the buffer is left as it was, including
+    /// any bytes it already had at `offset` and after.
+    pub fn pwrite_with<N: TryIntoCtx<Ctx, Error = Error> + Clone, Ctx: Copy>(
+        &mut self,
+        n: N,
+        offset: usize,
+        ctx: Ctx,
+    ) -> error::Result<usize> {
+        let len = self.buf.len();
+        // the bytes the attempts so far may have overwritten
+        let mut saved = Vec::new();
+        let blind = len.saturating_sub(offset).saturating_add(BLIND_GROWTH);
+        let mut room = 16;
+        let mut last = None;
+        let err = loop {
+            let Some(end) = offset.checked_add(room) else {
+                break Error::BadOffset(offset);
+            };
+            if let Err(err) = self.grow(end) {
+                break err;
+            }
+            if let Some(more) = self.buf.get(offset + saved.len()..cmp::min(len, end)) {
+                saved.extend_from_slice(more);
+            }
+            let err = match n.clone().try_into_ctx(&mut self.buf[offset..end], ctx) {
+                Ok(size) => {
+                    self.buf.truncate(cmp::max(len, offset + size));
+                    return Ok(size);
+                }
+                Err(err) => err,
+            };
+            match missing(&err, room, blind, &mut last) {
+                Some(missing) => {
+                    room = cmp::max(room.saturating_mul(2), room.saturating_add(missing))
+                }
+                None => break err,
+            }
+        };
+        self.buf.truncate(len);
+        if let Some(overwritten) = self.buf.get_mut(offset..offset + saved.len()) {
+            overwritten.copy_from_slice(&saved);
+        }
+        Err(N::offset_error(err, offset))
+    }
+
+    fn grow(&mut self, end: usize) -> error::Result<()> {
+        let additional = end.saturating_sub(self.buf.len());
+        self.buf
+            .try_reserve(additional)
+            .map_err(|_| Error::BadInput {
+                size: end,
+                msg: "can't allocate room for the value",
+            })?;
+        self.buf.resize(cmp::max(self.buf.len(), end), 0);
+        Ok(())
+    }
+
+    /// Writes `n` at `offset` with a default `Ctx`, and updates the offset
+    #[inline]
+    pub fn gwrite<N: TryIntoCtx<Ctx, Error = Error> + Clone, Ctx: Copy + Default>(
+        &mut self,
+        n: N,
+        offset: &mut usize,
+    ) -> error::Result<usize> {
+        self.gwrite_with(n, offset, Ctx::default())
+    }
+
+    /// Writes `n` at `offset` with the given `ctx`, and updates the offset
+    #[inline]
+    pub fn gwrite_with<N: TryIntoCtx<Ctx, Error = Error> + Clone, Ctx: Copy>(
+        &mut self,
+        n: N,
+        offset: &mut usize,
+        ctx: Ctx,
+    ) -> error::Result<usize> {
+        let o = *offset;
+        self.pwrite_with(n, o, ctx).inspect(|size| {
+            *offset += size;
+        })
+    }
+}
+
+/// How far a write may grow the buffer on `TooBig` errors which don't tell whether it got further
+const BLIND_GROWTH: usize = 1 << 20;
+
+/// How many more bytes a write which failed with `err` in a slice of `room` bytes is missing, if
+/// it ran into the end of the slice
+///
+/// The error came from a write into a slice `room` bytes long, so growing by what it was missing
+/// always makes progress. A write which ran into the end fails further along on every attempt,
+/// and one past the end fails at an offset beyond it; anything else fails the same way however
+/// large the slice gets. A `TooBig` with nothing left is retried while `room` is below `blind`
+/// even when it comes back the same, as element by element writes fail the same way each time
+/// they run out of room.
+fn missing(
+    err: &Error,
+    room: usize,
+    blind: usize,
+    last: &mut Option<(usize, usize, usize)>,
+) -> Option<usize> {
+    // the offset of the innermost value which failed, if the error says
+    let at = match err {
+        Error::Context(context) => context.offset(),
+        _ => 0,
+    };
+    match *err.root_cause() {
+        Error::TooBig { size, len } if size > len => {
+            let attempt = Some((at, size, len));
+            if *last == attempt && (len > 0 || room >= blind) {
+                return None;
+            }
+            *last = attempt;
+            Some(size - len)
+        }
+        // relative to the innermost value, whose offset is `at`
+        Error::BadOffset(offset) if cmp::max(at, offset) > room => {
+            Some(cmp::max(at, offset) - room)
+        }
+        _ => None,
+    }
+}
+
+impl From<Vec<u8>> for VecWriter {
+    /// Writes over (and past) the bytes of `buf`
+    #[inline]
+    fn from(buf: Vec<u8>) -> Self {
+        VecWriter { buf }
+    }
+}
+
+impl AsRef<[u8]> for VecWriter {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BE, LE, Pwrite, Uleb128};
+    use alloc::vec;
+
+    #[test]
+    fn vec_writer_grows() {
+        let mut writer = VecWriter::new();
+        let offset = &mut 0;
+        for i in 0..100u32 {
+            writer.gwrite_with(i, offset, LE).unwrap();
+        }
+        assert_eq!(writer.len(), 400);
+        assert_eq!(*offset, 400);
+        // a large write in one go
+        let bytes = [0xffu8; 100];
+        writer.gwrite(&bytes[..], offset).unwrap();
+        assert_eq!(writer.len(), 500);
+        // element by element writes fail the same way every time they run out of room
+        let mut elements = VecWriter::new();
+        assert_eq!(elements.pwrite_with(vec![0xaau8; 100], 0, LE).unwrap(), 100);
+        assert_eq!(elements.as_slice(), [0xaa; 100]);
+        // overwriting doesn't grow
+        writer.pwrite_with(0xdeadbeefu32, 0, BE).unwrap();
+        assert_eq!(writer.len(), 500);
+        assert_eq!(writer.as_slice()[..5], [0xde, 0xad, 0xbe, 0xef, 1]);
+        // nor does overwriting a value larger than the first attempt's slice
+        assert_eq!(writer.pwrite(&[0x11u8; 40][..], 8).unwrap(), 40);
+        assert_eq!(writer.len(), 500);
+        assert_eq!(writer.as_slice()[47..49], [0x11, 12]);
+    }
+
+    #[test]
+    fn vec_writer_zero_fills() {
+        let mut writer = VecWriter::from(vec![1, 2]);
+        writer.pwrite(Uleb128::from(300), 4).unwrap();
+        assert_eq!(writer.as_slice(), [1, 2, 0, 0, 0xac, 0x02]);
+    }
+
+    #[derive(Clone)]
+    struct Nested(u8);
+
+    impl TryIntoCtx for Nested {
+        type Error = Error;
+        fn try_into_ctx(self, dst: &mut [u8], _: ()) -> error::Result<usize> {
+            // skip over a gap like a padded struct would
+            dst.pwrite(self.0, 40)?;
+            Ok(41)
+        }
+    }
+
+    #[test]
+    fn vec_writer_nested_offsets() {
+        let mut writer = VecWriter::new();
+        assert_eq!(writer.pwrite(Nested(7), 2).unwrap(), 41);
+        assert_eq!(writer.len(), 43);
+        assert_eq!(writer.as_slice()[42], 7);
+    }
+
+    #[test]
+    fn vec_writer_errors() {
+        let mut writer = VecWriter::from(vec![1, 2]);
+        assert!(matches!(
+            writer.pwrite(crate::VarUint7::from(0xffu8), 4),
+            Err(Error::BadInput { .. })
+        ));
+        // the zero-filled gap is dropped again
+        assert_eq!(writer.as_slice(), [1, 2]);
+        // as are the bytes overwritten by a failed write
+        assert!(writer.pwrite(Failing(4), 0).is_err());
+        assert_eq!(writer.as_slice(), [1, 2]);
+        // one which never says how much is missing gives up, and restores the bytes too
+        assert!(matches!(
+            writer.pwrite(Failing(0), 1),
+            Err(Error::TooBig { size: 8, len: 0 })
+        ));
+        assert_eq!(writer.as_slice(), [1, 2]);
+        assert!(matches!(
+            writer.pwrite(0u8, usize::MAX),
+            Err(Error::BadOffset(usize::MAX))
+        ));
+    }
+
+    /// Writes a byte, then fails with a `TooBig` with `len` left which has nothing to do with
+    /// the buffer size
+    #[derive(Clone)]
+    struct Failing(usize);
+
+    impl TryIntoCtx for Failing {
+        type Error = Error;
+        fn try_into_ctx(self, dst: &mut [u8], _: ()) -> error::Result<usize> {
+            dst.pwrite(0xffu8, 0)?;
+            Err(Error::TooBig {
+                size: 8,
+                len: self.0,
+            })
+        }
+    }
+}
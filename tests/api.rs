@@ -1,4 +1,9 @@
 // this exists primarily to test various API usages of scroll; e.g., must compile
+#![allow(
+    clippy::new_without_default,
+    clippy::repr_packed_without_abi,
+    clippy::unit_arg
+)]
 
 use std::ops::{Deref, DerefMut};
 
@@ -212,6 +217,92 @@ fn ioread_api() {
     assert_eq!({ foo_.bar }, bar);
 }
 
+#[test]
+#[cfg(feature = "std")]
+fn ioread_unbounded_size() {
+    use std::io::{Cursor, ErrorKind};
+
+    use scroll::{IOread, IOwrite, LE};
+
+    /// A type claiming to be larger than any buffer could be
+    struct Unbounded;
+
+    impl scroll::ctx::FromCtx<scroll::Endian> for Unbounded {
+        fn from_ctx(_: &[u8], _: scroll::Endian) -> Self {
+            Unbounded
+        }
+    }
+
+    impl scroll::ctx::IntoCtx<scroll::Endian> for Unbounded {
+        fn into_ctx(self, _: &mut [u8], _: scroll::Endian) {}
+    }
+
+    impl scroll::ctx::SizeWith<scroll::Endian> for Unbounded {
+        fn size_with(_: &scroll::Endian) -> usize {
+            usize::MAX
+        }
+    }
+
+    let mut bytes = Cursor::new([0u8; 8]);
+    let err = bytes.ioread_with::<Unbounded>(LE).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+    let err = bytes.iowrite_with(Unbounded, LE).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+    assert_eq!(bytes.position(), 0);
+}
+
+/// A run of `u32`s with a `u16` count in front, whose size isn't known before reading it
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq)]
+struct Words(Vec<u32>);
+
+#[cfg(feature = "std")]
+impl<'a> ctx::TryFromCtx<'a, Endian> for Words {
+    type Error = scroll::Error;
+    fn try_from_ctx(src: &'a [u8], ctx: Endian) -> Result<(Self, usize)> {
+        let offset = &mut 0;
+        let count = src.gread_with::<u16>(offset, ctx)? as usize;
+        let words = src.gread_with(offset, ctx::CountCtx::new(count, ctx))?;
+        Ok((Words(words), *offset))
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn buf_ioread_api() {
+    use std::io::BufReader;
+
+    use scroll::{BE, BufIOread, IOwrite, Uleb128};
+
+    let values = [0, 127, 128, 624485, u64::MAX];
+    let words = [vec![], vec![1, 2, 0xdeadbeef]];
+    let mut bytes = Vec::new();
+    for n in values {
+        bytes.try_iowrite(Uleb128::from(n)).unwrap();
+    }
+    for words in &words {
+        bytes.try_iowrite_with(words.len() as u16, BE).unwrap();
+        bytes.try_iowrite_with(words, BE).unwrap();
+    }
+    bytes.try_iowrite_with(0xdeadbeefu32, BE).unwrap();
+    // a truncated Uleb128
+    bytes.push(0x80);
+    for capacity in [1, 3, 64] {
+        let mut reader = BufReader::with_capacity(capacity, &bytes[..]);
+        for n in values {
+            assert_eq!(u64::from(reader.try_ioread::<Uleb128>().unwrap()), n);
+        }
+        for words in &words {
+            assert_eq!(
+                reader.try_ioread_with::<Words>(BE).unwrap(),
+                Words(words.clone())
+            );
+        }
+        assert_eq!(reader.try_ioread_with::<u32>(BE).unwrap(), 0xdeadbeef);
+        assert!(reader.try_ioread::<Uleb128>().is_err());
+    }
+}
+
 #[repr(packed)]
 struct Bar {
     foo: i32,